@@ -0,0 +1,191 @@
+// Audit module for the append-only log of disk writes and sensitive operations
+// Every entry stores the hash of the previous entry and a SHA-256 over its own
+// fields, so editing or removing an entry breaks the chain from that point on.
+
+use sha2::{Digest, Sha256};
+use crate::models::{AuditAction, AuditEntry, AuditOutcome, AuditVerification};
+
+/// `prev_hash` of the first entry.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An audit entry before it is timestamped and chained.
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    pub action: AuditAction,
+    pub workspace_id: Option<i64>,
+    pub config_id: Option<i64>,
+    pub target_path: Option<String>,
+    pub hash_before: Option<String>,
+    pub hash_after: Option<String>,
+    pub outcome: AuditOutcome,
+    pub detail: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(action: AuditAction) -> Self {
+        AuditRecord {
+            action,
+            workspace_id: None,
+            config_id: None,
+            target_path: None,
+            hash_before: None,
+            hash_after: None,
+            outcome: AuditOutcome::Success,
+            detail: None,
+        }
+    }
+
+    /// Marks the record as failed when `result` is an error.
+    pub fn with_result<T>(mut self, result: &Result<T, String>) -> Self {
+        if let Err(e) = result {
+            self.outcome = AuditOutcome::Failure;
+            self.detail = Some(e.clone());
+        }
+        self
+    }
+
+    /// The entry recorded before the operation runs, so a write that is never
+    /// completed still leaves a trace.
+    pub fn pending(&self) -> Self {
+        AuditRecord {
+            outcome: AuditOutcome::Pending,
+            hash_after: None,
+            ..self.clone()
+        }
+    }
+
+    /// Links the final entry to the pending entry it completes.
+    pub fn completing(mut self, pending_id: i64) -> Self {
+        let link = format!("pending #{}", pending_id);
+        self.detail = Some(match self.detail {
+            Some(detail) => format!("{}; {}", detail, link),
+            None => link,
+        });
+        self
+    }
+
+    /// Chains the record onto `prev_hash`. The id is assigned on insert and is
+    /// not part of the hash.
+    pub fn into_entry(self, created_at: String, prev_hash: String) -> AuditEntry {
+        let mut entry = AuditEntry {
+            id: 0,
+            created_at,
+            actor: current_actor(),
+            action: self.action,
+            workspace_id: self.workspace_id,
+            config_id: self.config_id,
+            target_path: self.target_path,
+            hash_before: self.hash_before,
+            hash_after: self.hash_after,
+            outcome: self.outcome,
+            detail: self.detail,
+            prev_hash,
+            entry_hash: String::new(),
+        };
+        entry.entry_hash = compute_entry_hash(&entry);
+        entry
+    }
+}
+
+pub fn current_actor() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+pub fn compute_entry_hash(entry: &AuditEntry) -> String {
+    let fields = serde_json::json!([
+        entry.prev_hash,
+        entry.created_at,
+        entry.actor,
+        entry.action,
+        entry.workspace_id,
+        entry.config_id,
+        entry.target_path,
+        entry.hash_before,
+        entry.hash_after,
+        entry.outcome,
+        entry.detail,
+    ]);
+    let mut hasher = Sha256::new();
+    hasher.update(fields.to_string().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Checks the chain over all entries in id order.
+pub fn verify_chain(entries: &[AuditEntry]) -> AuditVerification {
+    let mut expected_prev = GENESIS_HASH;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.prev_hash != expected_prev || entry.entry_hash != compute_entry_hash(entry) {
+            return AuditVerification {
+                valid: false,
+                checked: i,
+                first_broken_id: Some(entry.id),
+            };
+        }
+        expected_prev = &entry.entry_hash;
+    }
+    AuditVerification {
+        valid: true,
+        checked: entries.len(),
+        first_broken_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(count: usize) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> = Vec::new();
+        for i in 0..count {
+            let prev = entries.last().map_or(GENESIS_HASH.to_string(), |e| e.entry_hash.clone());
+            let mut record = AuditRecord::new(AuditAction::WriteDirect);
+            record.target_path = Some(format!("/srv/app/{}.env", i));
+            let mut entry = record.into_entry("2026-01-01 00:00:00".to_string(), prev);
+            entry.id = i as i64 + 1;
+            entries.push(entry);
+        }
+        entries
+    }
+
+    #[test]
+    fn test_verify_chain_detects_tampering() {
+        let entries = chain(3);
+        assert!(verify_chain(&entries).valid);
+
+        let mut edited = entries.clone();
+        edited[1].outcome = AuditOutcome::Failure;
+        let result = verify_chain(&edited);
+        assert!(!result.valid);
+        assert_eq!(result.first_broken_id, Some(2));
+
+        let mut removed = entries.clone();
+        removed.remove(1);
+        assert_eq!(verify_chain(&removed).first_broken_id, Some(3));
+    }
+
+    #[test]
+    fn test_failed_result_is_recorded() {
+        let record = AuditRecord::new(AuditAction::DeleteConfig).with_result::<()>(&Err("disk full".to_string()));
+        assert_eq!(record.outcome, AuditOutcome::Failure);
+        assert_eq!(record.detail.as_deref(), Some("disk full"));
+    }
+
+    #[test]
+    fn test_pending_record_is_completed_by_final_record() {
+        let mut record = AuditRecord::new(AuditAction::WriteDirect);
+        record.hash_after = Some("after".to_string());
+
+        let pending = record.pending();
+        assert_eq!(pending.outcome, AuditOutcome::Pending);
+        assert_eq!(pending.hash_after, None);
+
+        let done = record.clone().completing(7);
+        assert_eq!(done.outcome, AuditOutcome::Success);
+        assert_eq!(done.detail.as_deref(), Some("pending #7"));
+
+        let failed = record.with_result::<()>(&Err("disk full".to_string())).completing(7);
+        assert_eq!(failed.detail.as_deref(), Some("disk full; pending #7"));
+    }
+}
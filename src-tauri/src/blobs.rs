@@ -0,0 +1,34 @@
+// Blob module for stored config contents
+// Contents are kept once per SHA-256 hash (see `file_system::calculate_content_hash`)
+// and compressed with zstd; the database layer handles storage and lookup.
+
+use std::io;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+pub fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::encode_all(data, COMPRESSION_LEVEL)
+}
+
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::decode_all(data)
+}
+
+/// Decompresses a blob holding text content.
+pub fn decompress_text(data: &[u8]) -> io::Result<String> {
+    String::from_utf8(decompress(data)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_round_trip() {
+        let content = "DATABASE_URL=postgres://localhost/app\n".repeat(100);
+        let compressed = compress(content.as_bytes()).unwrap();
+        assert!(compressed.len() < content.len() / 10);
+        assert_eq!(decompress_text(&compressed).unwrap(), content);
+        assert!(decompress_text(b"not zstd").is_err());
+    }
+}
@@ -1,11 +1,34 @@
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 use sqlx::SqlitePool;
-use std::sync::Mutex;
-use crate::models::{Config, CreateConfig, FileStatus, MergeData, Workspace};
-use crate::{db, file_system, sanitizer};
+use std::sync::{LazyLock, Mutex};
+use crate::models::{
+    AuditAction, AuditEntry, AuditQuery, AuditVerification, Config, ConfigDiff, ConfigKind, ConfigSummary, ConfigRevision,
+    ConfigSet, ConfigSetSync, CreateConfig, DiffSource, FileBackup, FileStatus, FormatOverride, ImportedFile,
+    KeyOverride, LineRule, MergeData, RevisionField, RevisionSource, RevisionSummary, SanitizeProfile, SanitizerScript, SemanticMergeData,
+    RestoreCollision, SnapshotChange, SnapshotConfigDiff, SnapshotRestore, Workspace, WorkspaceSnapshot, WriteConflict, WriteError, WriteMode,
+};
+use crate::{audit, db, diff, discovery, encoding, file_system, merger, sanitizer, scripting, watcher};
+use crate::audit::AuditRecord;
+use crate::discovery::ConfigCandidate;
+use crate::file_system::{FileSystemError, SymlinkPolicy};
+use crate::merger::{ConflictSide, SyncStatus};
+use crate::sanitizer::{MaskPolicy, SanitizerEntry, SanitizerRegistry};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use crate::watcher::{WatchFailure, WorkspaceWatcher};
 
 pub struct AppState {
     pub pool: Mutex<Option<SqlitePool>>,
+    pub watcher: Mutex<Option<WorkspaceWatcher>>,
+    /// Watch failures from startup, emitted before the frontend could listen.
+    pub startup_failures: Mutex<Vec<WatchFailure>>,
+}
+
+/// Hands over the watch failures from startup once; later ones arrive as
+/// `watch-failed` events.
+#[tauri::command]
+pub fn take_startup_failures(state: State<'_, AppState>) -> Vec<WatchFailure> {
+    std::mem::take(&mut *state.startup_failures.lock().unwrap())
 }
 
 // --- Workspace commands ---
@@ -18,9 +41,25 @@ pub async fn add_workspace(
 ) -> Result<i64, String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
-    db::add_workspace(&pool, &name, &root_path)
+    let id = db::add_workspace(&pool, &name, &root_path)
         .await
-        .map_err(|e| format!("添加工作区失败: {}", e))
+        .map_err(|e| format!("添加工作区失败: {}", e))?;
+
+    rewatch(&state, &pool, id).await;
+    Ok(id)
+}
+
+/// Points the watcher at the directories a workspace's configs and sets cover
+/// now. A workspace that cannot be watched still works, just without live
+/// updates; the watcher reports why.
+pub(crate) async fn rewatch(state: &AppState, pool: &SqlitePool, workspace_id: i64) {
+    let Ok(Some(workspace)) = db::get_workspace_by_id(pool, workspace_id).await else {
+        return;
+    };
+    let dirs = watcher::watched_dirs(pool, workspace_id, &workspace.root_path).await;
+    if let Some(watcher) = state.watcher.lock().unwrap().as_ref() {
+        watcher.watch(workspace_id, &workspace.root_path, &dirs);
+    }
 }
 
 #[tauri::command]
@@ -36,9 +75,44 @@ pub async fn get_all_workspaces(state: State<'_, AppState>) -> Result<Vec<Worksp
 pub async fn delete_workspace(id: i64, state: State<'_, AppState>) -> Result<(), String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
-    db::delete_workspace(&pool, id)
+    let workspace = db::get_workspace_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取工作区失败: {}", e))?;
+
+    let result = db::delete_workspace(&pool, id)
+        .await
+        .map_err(|e| format!("删除工作区失败: {}", e));
+
+    let mut record = AuditRecord::new(AuditAction::DeleteWorkspace);
+    record.workspace_id = Some(id);
+    if let Some(watcher) = state.watcher.lock().unwrap().as_ref() {
+        watcher.unwatch(id);
+    }
+    record.target_path = workspace.map(|w| w.root_path);
+    audit(&pool, record.with_result(&result)).await?;
+    result
+}
+
+/// Sets how paths that are symlinks are read and written in a workspace.
+#[tauri::command]
+pub async fn set_symlink_policy(
+    workspace_id: i64,
+    policy: SymlinkPolicy,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::set_symlink_policy(&pool, workspace_id, policy)
+        .await
+        .map_err(|e| format!("更新符号链接策略失败: {}", e))
+}
+
+async fn symlink_policy(pool: &SqlitePool, workspace_id: i64) -> Result<SymlinkPolicy, String> {
+    let workspace = db::get_workspace_by_id(pool, workspace_id)
         .await
-        .map_err(|e| format!("删除工作区失败: {}", e))
+        .map_err(|e| format!("获取工作区失败: {}", e))?
+        .ok_or("工作区不存在")?;
+    Ok(workspace.symlink_policy)
 }
 
 // --- Config commands ---
@@ -53,23 +127,112 @@ pub async fn add_config(
 ) -> Result<i64, String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
-    let content = file_system::read_file(&workspace_root, &relative_path)
+    let relative_path = file_system::normalize_path(&relative_path);
+    let policy = symlink_policy(&pool, workspace_id).await?;
+    let bytes = file_system::read_file_bytes(&workspace_root, &relative_path, policy)
         .map_err(|e| format!("读取文件失败: {}", e))?;
 
+    // Opaque files are stored as is and have no revision history
+    if file_system::is_binary_file(&relative_path, &bytes) {
+        let id = db::add_binary_config(&pool, workspace_id, &name, &relative_path, &bytes)
+            .await
+            .map_err(|e| format!("添加配置失败: {}", e))?;
+        rewatch(&state, &pool, workspace_id).await;
+        return Ok(id);
+    }
+
+    let (content, encoding) = encoding::decode(&bytes);
     let config = CreateConfig {
         workspace_id,
         name,
         path: relative_path,
-        original_content: content,
+        original_content: content.clone(),
+        encoding,
     };
 
-    db::add_config(&pool, config)
+    let id = db::add_config(&pool, config)
+        .await
+        .map_err(|e| format!("添加配置失败: {}", e))?;
+
+    db::record_revision(&pool, id, RevisionField::Original, RevisionSource::Import, Some(&content))
+        .await
+        .map_err(|e| format!("记录历史版本失败: {}", e))?;
+    rewatch(&state, &pool, workspace_id).await;
+    Ok(id)
+}
+
+/// Lists the files in a workspace that look like configs, most likely to
+/// hold secrets first, and marks the ones already tracked.
+#[tauri::command]
+pub async fn scan_workspace(
+    workspace_id: i64,
+    workspace_root: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<ConfigCandidate>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let tracked = tracked_paths(&pool, workspace_id).await?;
+
+    let mut candidates = discovery::scan(&workspace_root).map_err(|e| format!("扫描工作区失败: {}", e))?;
+    for candidate in &mut candidates {
+        candidate.tracked = tracked.contains(&candidate.path);
+    }
+    Ok(candidates)
+}
+
+/// Imports the selected files as configs named after their workspace-relative
+/// path. Paths already tracked are skipped; the rest are imported in one transaction, so
+/// one unreadable file imports nothing. Returns the new config ids.
+#[tauri::command]
+pub async fn import_configs(
+    workspace_id: i64,
+    workspace_root: String,
+    paths: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let tracked = tracked_paths(&pool, workspace_id).await?;
+    let policy = symlink_policy(&pool, workspace_id).await?;
+
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for path in paths.iter().map(|path| file_system::normalize_path(path)) {
+        if tracked.contains(&path) || !seen.insert(path.clone()) {
+            continue;
+        }
+        let bytes = file_system::read_file_bytes(&workspace_root, &path, policy)
+            .map_err(|e| format!("读取文件 {} 失败: {}", path, e))?;
+        files.push(imported_file(workspace_id, path, bytes));
+    }
+
+    let ids = db::import_configs(&pool, &files)
+        .await
+        .map_err(|e| format!("导入配置失败: {}", e))?;
+    rewatch(&state, &pool, workspace_id).await;
+    Ok(ids)
+}
+
+/// A file read for import, named after its workspace-relative path so files
+/// of the same name in different directories stay apart.
+fn imported_file(workspace_id: i64, path: String, bytes: Vec<u8>) -> ImportedFile {
+    let name = path.clone();
+    if file_system::is_binary_file(&path, &bytes) {
+        return ImportedFile::Binary { workspace_id, name, path, content: bytes };
+    }
+    let (original_content, encoding) = encoding::decode(&bytes);
+    ImportedFile::Text(CreateConfig { workspace_id, name, path, original_content, encoding })
+}
+
+async fn tracked_paths(pool: &SqlitePool, workspace_id: i64) -> Result<HashSet<String>, String> {
+    let hashes = db::get_config_hashes(pool, workspace_id)
         .await
-        .map_err(|e| format!("添加配置失败: {}", e))
+        .map_err(|e| format!("获取配置列表失败: {}", e))?;
+    Ok(hashes.into_iter().map(|hashes| file_system::normalize_path(&hashes.path)).collect())
 }
 
 #[tauri::command]
-pub async fn get_all_configs(state: State<'_, AppState>) -> Result<Vec<Config>, String> {
+pub async fn get_all_configs(state: State<'_, AppState>) -> Result<Vec<ConfigSummary>, String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
     db::get_all_configs(&pool)
@@ -94,21 +257,165 @@ pub async fn check_file_status(
 ) -> Result<FileStatus, String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
-    let config = db::get_config_by_id(&pool, id)
+    let hashes = db::get_config_hashes_by_id(&pool, id)
         .await
         .map_err(|e| format!("获取配置失败: {}", e))?
         .ok_or("配置不存在")?;
 
-    let disk_hash = file_system::calculate_file_hash(&workspace_root, &config.path)
+    let policy = symlink_policy(&pool, hashes.workspace_id).await?;
+    let disk_hash = disk_hash(&workspace_root, &hashes.path, hashes.kind, policy)
         .map_err(|e| format!("计算文件哈希失败: {}", e))?;
 
-    let db_hash = file_system::calculate_content_hash(&config.original_content);
-
+    let status = hashes.status(disk_hash.as_deref());
     Ok(FileStatus {
-        has_external_changes: disk_hash != db_hash,
+        status,
+        has_external_changes: matches!(status, SyncStatus::DiskAhead | SyncStatus::Diverged),
         current_hash: disk_hash,
+        symlink_target: file_system::symlink_target(&workspace_root, &hashes.path).map(|p| p.display().to_string()),
     })
 }
+
+/// Untracked files holding a missing config's original, last-synced or
+/// sanitized output content, i.e. where it was probably renamed or moved to.
+/// Only files with the config's extension and an expected size are hashed,
+/// and the scan runs on a blocking thread.
+#[tauri::command]
+pub async fn find_moved_file(
+    id: i64,
+    workspace_root: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    let hashes = db::get_config_hashes_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    let known: Vec<String> = [Some(hashes.original_hash), hashes.synced_hash, hashes.sanitized_output_hash]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    // The stored form and, for text, the form written back to disk
+    let mut sizes = Vec::new();
+    for hash in &known {
+        let content = db::get_blob_bytes(&pool, hash)
+            .await
+            .map_err(|e| format!("读取内容失败: {}", e))?;
+        let Some(content) = content else {
+            continue;
+        };
+        sizes.push(content.len() as u64);
+        if config.kind == ConfigKind::Text {
+            if let Ok(encoded) = encoding::encode(&String::from_utf8_lossy(&content), &config.encoding) {
+                sizes.push(encoded.len() as u64);
+            }
+        }
+    }
+
+    let query = discovery::MovedFileQuery {
+        kind: config.kind,
+        extension: discovery::extension(&config.path),
+        sizes,
+        hashes: known,
+    };
+    let root = workspace_root.clone();
+    let paths = tauri::async_runtime::spawn_blocking(move || discovery::find_by_hash(&root, &query))
+        .await
+        .map_err(|e| format!("扫描工作区失败: {}", e))?
+        .map_err(|e| format!("扫描工作区失败: {}", e))?;
+
+    let tracked = tracked_paths(&pool, config.workspace_id).await?;
+    Ok(paths.into_iter().filter(|path| !tracked.contains(path)).collect())
+}
+
+/// Points a config at the path its file was renamed or moved to. A config
+/// named after its path, or after its file name as older imports were, is
+/// renamed along with it.
+#[tauri::command]
+pub async fn relink_config(
+    id: i64,
+    workspace_root: String,
+    new_path: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    let new_path = file_system::normalize_path(&new_path);
+    let policy = symlink_policy(&pool, config.workspace_id).await?;
+    if !file_system::file_exists(&workspace_root, &new_path, policy) {
+        return Err(format!("文件不存在: {}", new_path));
+    }
+    if tracked_paths(&pool, config.workspace_id).await?.contains(&new_path) {
+        return Err(format!("该路径已被其他配置使用: {}", new_path));
+    }
+
+    let file_name = |path: &str| Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned());
+    let name = if config.name == config.path {
+        new_path.clone()
+    } else if file_name(&config.path).as_deref() == Some(config.name.as_str()) {
+        file_name(&new_path).unwrap_or(config.name.clone())
+    } else {
+        config.name.clone()
+    };
+    let result = db::update_config_path(&pool, id, &name, &new_path)
+        .await
+        .map_err(|e| format!("更新配置路径失败: {}", e));
+
+    let mut record = AuditRecord::new(AuditAction::RelinkConfig);
+    record.workspace_id = Some(config.workspace_id);
+    record.config_id = Some(id);
+    record.target_path = Some(Path::new(&workspace_root).join(&new_path).display().to_string());
+    record.detail = Some(format!("from {}", config.path));
+    audit(&pool, record.with_result(&result)).await?;
+    rewatch(&state, &pool, config.workspace_id).await;
+    result
+}
+
+/// Writes a missing config's file back from its stored content, creating
+/// directories as needed. A file that still exists is left to the normal
+/// writes, which check it for conflicts.
+#[tauri::command]
+pub async fn recreate_config_file(
+    id: i64,
+    workspace_root: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    let policy = symlink_policy(&pool, config.workspace_id).await?;
+    let current_hash = disk_hash(&workspace_root, &config.path, config.kind, policy)
+        .map_err(|e| format!("读取文件失败: {}", e))?;
+    if current_hash.is_some() {
+        return Err("文件仍存在，请使用写入功能".to_string());
+    }
+
+    let output = direct_output(&pool, &config).await?;
+    write_config_file(&app, &pool, &config, &workspace_root, &output.bytes, AuditAction::WriteDirect, Some("recreate".to_string()))
+        .await
+        .map_err(|e| format!("写入文件失败: {}", e))?;
+
+    db::update_synced_hash(&pool, id, &output.hash)
+        .await
+        .map_err(|e| format!("更新同步基线失败: {}", e))?;
+    // The file's directory may have been created just now
+    rewatch(&state, &pool, config.workspace_id).await;
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn get_config_for_merge(
     id: i64,
@@ -121,27 +428,280 @@ pub async fn get_config_for_merge(
         .await
         .map_err(|e| format!("获取配置失败: {}", e))?
         .ok_or("配置不存在")?;
+    require_text(&config)?;
 
-    let disk_content = file_system::read_file(&workspace_root, &config.path)
+    let policy = symlink_policy(&pool, config.workspace_id).await?;
+    let disk_content = file_system::read_file(&workspace_root, &config.path, policy)
         .map_err(|e| format!("读取文件失败: {}", e))?;
 
+    merge_data(&pool, &config, disk_content).await
+}
+
+async fn merge_data(pool: &SqlitePool, config: &Config, disk_content: String) -> Result<MergeData, String> {
+    let base_content = db::get_synced_content(pool, config.id)
+        .await
+        .map_err(|e| format!("获取同步基线失败: {}", e))?;
+
+    // Without a recorded base every difference is treated as a conflict
+    let merge = merger::merge_three_way(
+        base_content.as_deref().unwrap_or(""),
+        &config.original_content,
+        &disk_content,
+    );
+
     Ok(MergeData {
-        db_content: config.original_content,
+        db_content: config.original_content.clone(),
+        disk_content,
+        base_content,
+        merged_content: merge.merged,
+        has_conflicts: merge.has_conflicts,
+        hunks: merge.hunks,
+    })
+}
+
+/// Runs a key-path-level merge for a config whose format can be parsed.
+async fn semantic_merge(
+    pool: &SqlitePool,
+    config: &Config,
+    workspace_root: &str,
+    resolutions: &HashMap<String, ConflictSide>,
+) -> Result<SemanticMergeData, String> {
+    require_text(config)?;
+    let registry = load_registry(pool, config.workspace_id, Some(&config.path), &[]).await?;
+    let format = registry
+        .resolve(&config.path, &config.original_content)
+        .and_then(|s| merger::SemanticFormat::from_name(s.name()))
+        .ok_or("该格式不支持按键合并")?;
+
+    let policy = symlink_policy(pool, config.workspace_id).await?;
+    let disk_content = file_system::read_file(workspace_root, &config.path, policy)
+        .map_err(|e| format!("读取文件失败: {}", e))?;
+
+    let base_content = db::get_synced_content(pool, config.id)
+        .await
+        .map_err(|e| format!("获取同步基线失败: {}", e))?;
+
+    let merge = merger::merge_semantic(
+        base_content.as_deref().unwrap_or(""),
+        &config.original_content,
+        &disk_content,
+        format,
+        resolutions,
+    )
+    .map_err(|e| format!("合并失败: {}", e))?;
+
+    Ok(SemanticMergeData {
+        db_content: config.original_content.clone(),
         disk_content,
+        base_content,
+        merged_content: merge.merged,
+        conflicts: merge.conflicts,
     })
 }
 
+#[tauri::command]
+pub async fn get_semantic_merge(
+    id: i64,
+    workspace_root: String,
+    state: State<'_, AppState>,
+) -> Result<SemanticMergeData, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+
+    semantic_merge(&pool, &config, &workspace_root, &HashMap::new()).await
+}
+
+/// Applies a semantic merge with the chosen side for each conflicting key and
+/// stores the result as the new original content. Refused merges are audited
+/// as failures.
+#[tauri::command]
+pub async fn resolve_semantic_merge(
+    id: i64,
+    workspace_root: String,
+    resolutions: HashMap<String, ConflictSide>,
+    allow_masked: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    let allow_masked = allow_masked.unwrap_or(false);
+
+    let result = apply_semantic_merge(&pool, &config, &workspace_root, &resolutions, allow_masked).await;
+
+    let mut record = AuditRecord::new(AuditAction::ResolveConflict);
+    record.workspace_id = Some(config.workspace_id);
+    record.config_id = Some(id);
+    record.target_path = Some(config.path.clone());
+    record.hash_before = Some(file_system::calculate_content_hash(&config.original_content));
+    record.hash_after = result.as_deref().ok().map(file_system::calculate_content_hash);
+    record.detail = Some(if allow_masked { "semantic, masked allowed" } else { "semantic" }.to_string());
+    audit(&pool, record.with_result(&result)).await?;
+    result
+}
+
+/// Checks a semantic merge has no conflicts left, is not a masked copy unless
+/// allowed and still parses, then stores it. Returns the merged content.
+async fn apply_semantic_merge(
+    pool: &SqlitePool,
+    config: &Config,
+    workspace_root: &str,
+    resolutions: &HashMap<String, ConflictSide>,
+    allow_masked: bool,
+) -> Result<String, String> {
+    let merge = semantic_merge(pool, config, workspace_root, resolutions).await?;
+    if !merge.conflicts.is_empty() {
+        let keys: Vec<&str> = merge.conflicts.iter().map(|c| c.key.as_str()).collect();
+        return Err(format!("仍有未解决的冲突: {}", keys.join(", ")));
+    }
+    if !allow_masked {
+        check_not_masked(pool, config, &merge.merged_content).await?;
+    }
+    validate_content(pool, config, &merge.merged_content).await?;
+
+    save_content(pool, config, RevisionField::Original, Some(&merge.merged_content), RevisionSource::Merge).await?;
+    db::update_synced_content(pool, config.id, &merge.disk_content)
+        .await
+        .map_err(|e| format!("更新同步基线失败: {}", e))?;
+    Ok(merge.merged_content)
+}
+
 #[tauri::command]
 pub async fn resolve_conflict(
     id: i64,
     merged_content: String,
+    workspace_root: Option<String>,
+    force: Option<bool>,
+    allow_masked: Option<bool>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
-    db::update_original_content(&pool, id, &merged_content)
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    let force = force.unwrap_or(false);
+    let allow_masked = allow_masked.unwrap_or(false);
+
+    let result = apply_conflict_resolution(&pool, &config, &merged_content, workspace_root, force, allow_masked).await;
+
+    let mut record = AuditRecord::new(AuditAction::ResolveConflict);
+    record.workspace_id = Some(config.workspace_id);
+    record.config_id = Some(id);
+    record.target_path = Some(config.path.clone());
+    record.hash_before = Some(file_system::calculate_content_hash(&config.original_content));
+    record.hash_after = Some(file_system::calculate_content_hash(&merged_content));
+    let detail: Vec<&str> = [(force, "forced"), (allow_masked, "masked allowed")]
+        .into_iter()
+        .filter_map(|(set, label)| set.then_some(label))
+        .collect();
+    record.detail = (!detail.is_empty()).then(|| detail.join(", "));
+    audit(&pool, record.with_result(&result)).await?;
+    result
+}
+
+/// Validates a manual merge result unless forced, and checks it is not a
+/// masked copy unless that is explicitly allowed, then stores it.
+async fn apply_conflict_resolution(
+    pool: &SqlitePool,
+    config: &Config,
+    merged_content: &str,
+    workspace_root: Option<String>,
+    force: bool,
+    allow_masked: bool,
+) -> Result<(), String> {
+    require_text(config)?;
+    if !force {
+        let markers = merger::find_conflict_markers(merged_content);
+        if !markers.is_empty() {
+            let lines: Vec<String> = markers.iter().map(|line| line.to_string()).collect();
+            return Err(format!("合并结果仍包含冲突标记 (第 {} 行)", lines.join(", ")));
+        }
+        validate_content(pool, config, merged_content).await?;
+    }
+    if !allow_masked {
+        check_not_masked(pool, config, merged_content).await?;
+    }
+
+    let disk_content = match workspace_root {
+        Some(root) => {
+            let policy = symlink_policy(pool, config.workspace_id).await?;
+            Some(file_system::read_file(&root, &config.path, policy).map_err(|e| format!("读取文件失败: {}", e))?)
+        }
+        None => None,
+    };
+
+    // Taking the disk version unchanged is a pull rather than a merge
+    let source = if disk_content.as_deref() == Some(merged_content) {
+        RevisionSource::DiskPull
+    } else {
+        RevisionSource::Merge
+    };
+    save_content(pool, config, RevisionField::Original, Some(merged_content), source).await?;
+
+    // The disk version has now been merged in, so it becomes the next base
+    if let Some(disk_content) = disk_content {
+        db::update_synced_content(pool, config.id, &disk_content)
+            .await
+            .map_err(|e| format!("更新同步基线失败: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Refuses content that would replace an original with masked values: either
+/// an exact sanitized output this app wrote for the config, or content with
+/// more placeholders than the current original. Placeholders only count once
+/// the config has a sanitized counterpart; before that a literal `***` is just
+/// content.
+async fn check_not_masked(pool: &SqlitePool, config: &Config, content: &str) -> Result<(), String> {
+    let hash = file_system::calculate_content_hash(content);
+    let is_output = db::is_sanitized_output(pool, config.id, &hash)
+        .await
+        .map_err(|e| format!("检查脱敏输出失败: {}", e))?;
+    if is_output {
+        return Err("内容与已写出的脱敏结果相同，不能覆盖原始内容 (可确认后保留)".to_string());
+    }
+
+    let has_counterpart = config.sanitized_content.is_some()
+        || db::has_sanitized_outputs(pool, config.id)
+            .await
+            .map_err(|e| format!("检查脱敏输出失败: {}", e))?;
+    if !has_counterpart {
+        return Ok(());
+    }
+
+    let profiles = db::get_all_profiles(pool)
         .await
-        .map_err(|e| format!("更新内容失败: {}", e))
+        .map_err(|e| format!("获取脱敏配置方案失败: {}", e))?;
+    let default_placeholder = MaskPolicy::default().placeholder;
+    let mut placeholders: Vec<&str> = profiles.iter().map(|p| p.placeholder.as_str()).collect();
+    placeholders.push(&default_placeholder);
+    placeholders.sort_unstable();
+    placeholders.dedup();
+
+    let found = sanitizer::introduced_placeholders(&config.original_content, content, &placeholders);
+    if !found.is_empty() {
+        return Err(format!("内容包含脱敏占位符 {}，疑似脱敏结果 (可确认后保留)", found.join(", ")));
+    }
+    Ok(())
+}
+
+/// Checks that `content` still parses in the config's format. Formats without
+/// a sanitizer cannot be checked and are accepted.
+async fn validate_content(pool: &SqlitePool, config: &Config, content: &str) -> Result<(), String> {
+    let registry = load_registry(pool, config.workspace_id, Some(&config.path), &[]).await?;
+    match registry.validate(content, &config.path) {
+        Ok(()) | Err(sanitizer::SanitizerError::UnsupportedFormat(_)) => Ok(()),
+        Err(e) => Err(format!("合并结果格式无效: {}", e)),
+    }
 }
 
 #[tauri::command]
@@ -152,9 +712,12 @@ pub async fn update_original_content(
 ) -> Result<(), String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
-    db::update_original_content(&pool, id, &content)
+    let config = db::get_config_by_id(&pool, id)
         .await
-        .map_err(|e| format!("更新内容失败: {}", e))
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+
+    save_content(&pool, &config, RevisionField::Original, Some(&content), RevisionSource::Editor).await
 }
 
 #[tauri::command]
@@ -165,16 +728,44 @@ pub async fn update_sanitized_content(
 ) -> Result<(), String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
-    db::update_sanitized_content(&pool, id, content.as_deref())
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+
+    save_content(&pool, &config, RevisionField::Sanitized, content.as_deref(), RevisionSource::Editor).await
+}
+
+/// Stores new content for one field of a config and records it in the
+/// revision history. `content` is only `None` when clearing sanitized content.
+async fn save_content(
+    pool: &SqlitePool,
+    config: &Config,
+    field: RevisionField,
+    content: Option<&str>,
+    source: RevisionSource,
+) -> Result<(), String> {
+    require_text(config)?;
+    // Configs saved before history was kept get their current content recorded
+    // first, so the first edit can still be rolled back.
+    let previous = match field {
+        RevisionField::Original => Some(config.original_content.as_str()),
+        RevisionField::Sanitized => config.sanitized_content.as_deref(),
+    };
+    db::save_content(pool, config.id, field, previous, content, source)
         .await
-        .map_err(|e| format!("更新脱敏内容失败: {}", e))
+        .map_err(|e| format!("保存内容失败: {}", e))
 }
+
 #[tauri::command]
 pub async fn write_to_file_direct(
     id: i64,
     workspace_root: String,
+    expected_hash: Option<String>,
+    force: Option<bool>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), WriteError> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
     let config = db::get_config_by_id(&pool, id)
@@ -182,16 +773,29 @@ pub async fn write_to_file_direct(
         .map_err(|e| format!("获取配置失败: {}", e))?
         .ok_or("配置不存在")?;
 
-    file_system::write_file(&workspace_root, &config.path, &config.original_content)
-        .map_err(|e| format!("写入文件失败: {}", e))
+    let output = direct_output(&pool, &config).await?;
+    let targets = [(&config, &output)];
+    let detail = guard_writes(&pool, &targets, &workspace_root, expected_hash.as_deref(), force, AuditAction::WriteDirect).await?;
+
+    write_config_file(&app, &pool, &config, &workspace_root, &output.bytes, AuditAction::WriteDirect, detail)
+        .await
+        .map_err(|e| format!("写入文件失败: {}", e))?;
+
+    db::update_synced_hash(&pool, id, &output.hash)
+        .await
+        .map_err(|e| format!("更新同步基线失败: {}", e).into())
 }
 
 #[tauri::command]
 pub async fn write_to_file_sanitized(
     id: i64,
     workspace_root: String,
+    profile: Option<String>,
+    expected_hash: Option<String>,
+    force: Option<bool>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), WriteError> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
     let config = db::get_config_by_id(&pool, id)
@@ -199,87 +803,366 @@ pub async fn write_to_file_sanitized(
         .map_err(|e| format!("获取配置失败: {}", e))?
         .ok_or("配置不存在")?;
 
-    let sanitized_content = if let Some(manual_content) = config.sanitized_content {
-        manual_content
-    } else {
-        match sanitizer::sanitize_content(&config.original_content, &config.path) {
-            Ok(sanitized) => sanitized,
-            Err(crate::sanitizer::SanitizerError::UnsupportedFormat(_)) => {
-                config.original_content
-            }
-            Err(e) => return Err(format!("脱敏失败: {}", e)),
-        }
-    };
+    let output = sanitized_output(&pool, &config, profile.as_deref())
+        .await?
+        .ok_or("二进制配置没有提供替换文件，不能写入脱敏版本")?;
+    let targets = [(&config, &output)];
+    let forced = guard_writes(&pool, &targets, &workspace_root, expected_hash.as_deref(), force, AuditAction::WriteSanitized).await?;
+    let detail = write_detail(profile.as_deref(), forced);
 
-    file_system::write_file(&workspace_root, &config.path, &sanitized_content)
-        .map_err(|e| format!("写入文件失败: {}", e))
+    write_config_file(&app, &pool, &config, &workspace_root, &output.bytes, AuditAction::WriteSanitized, detail)
+        .await
+        .map_err(|e| format!("写入文件失败: {}", e))?;
+
+    db::record_sanitized_output(&pool, id, &output.hash, profile.as_deref())
+        .await
+        .map_err(|e| format!("更新脱敏输出哈希失败: {}", e).into())
 }
 
+/// Writes every config in the workspace. Conflicts are checked for all files
+/// before anything is written, so a refused write leaves the disk untouched.
 #[tauri::command]
 pub async fn write_workspace_direct(
     workspace_id: i64,
     workspace_root: String,
+    force: Option<bool>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<usize, String> {
+) -> Result<usize, WriteError> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
     let configs = db::get_configs_by_workspace(&pool, workspace_id)
         .await
         .map_err(|e| format!("获取配置列表失败: {}", e))?;
 
-    let mut count = 0;
+    let mut writes = Vec::with_capacity(configs.len());
     for config in &configs {
-        file_system::write_file(&workspace_root, &config.path, &config.original_content)
+        writes.push((config, direct_output(&pool, config).await?));
+    }
+    let targets: Vec<(&Config, &FileOutput)> = writes.iter().map(|(c, o)| (*c, o)).collect();
+    let detail = guard_writes(&pool, &targets, &workspace_root, None, force, AuditAction::WriteDirect).await?;
+
+    let mut count = 0;
+    for (config, output) in targets {
+        write_config_file(&app, &pool, config, &workspace_root, &output.bytes, AuditAction::WriteDirect, detail.clone())
+            .await
             .map_err(|e| format!("写入文件 {} 失败: {}", config.path, e))?;
+        db::update_synced_hash(&pool, config.id, &output.hash)
+            .await
+            .map_err(|e| format!("更新同步基线失败: {}", e))?;
         count += 1;
     }
     Ok(count)
 }
 
+/// Writes the sanitized version of every config in the workspace. Binary
+/// configs without a replacement file are skipped.
 #[tauri::command]
 pub async fn write_workspace_sanitized(
     workspace_id: i64,
     workspace_root: String,
+    profile: Option<String>,
+    force: Option<bool>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<usize, String> {
+) -> Result<usize, WriteError> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
 
     let configs = db::get_configs_by_workspace(&pool, workspace_id)
         .await
         .map_err(|e| format!("获取配置列表失败: {}", e))?;
 
-    let mut count = 0;
+    let mut writes = Vec::with_capacity(configs.len());
     for config in &configs {
-        let sanitized_content = if let Some(ref manual_content) = config.sanitized_content {
-            manual_content.clone()
-        } else {
-            match sanitizer::sanitize_content(&config.original_content, &config.path) {
-                Ok(sanitized) => sanitized,
-                Err(crate::sanitizer::SanitizerError::UnsupportedFormat(_)) => {
-                    config.original_content.clone()
-                }
-                Err(e) => return Err(format!("脱敏文件 {} 失败: {}", config.path, e)),
-            }
-        };
-        file_system::write_file(&workspace_root, &config.path, &sanitized_content)
+        let output = sanitized_output(&pool, config, profile.as_deref())
+            .await
+            .map_err(|e| format!("{} ({})", e, config.path))?;
+        if let Some(output) = output {
+            writes.push((config, output));
+        }
+    }
+    let targets: Vec<(&Config, &FileOutput)> = writes.iter().map(|(c, o)| (*c, o)).collect();
+    let forced = guard_writes(&pool, &targets, &workspace_root, None, force, AuditAction::WriteSanitized).await?;
+    let detail = write_detail(profile.as_deref(), forced);
+
+    let mut count = 0;
+    for (config, output) in targets {
+        write_config_file(&app, &pool, config, &workspace_root, &output.bytes, AuditAction::WriteSanitized, detail.clone())
+            .await
             .map_err(|e| format!("写入文件 {} 失败: {}", config.path, e))?;
+        db::record_sanitized_output(&pool, config.id, &output.hash, profile.as_deref())
+            .await
+            .map_err(|e| format!("更新脱敏输出哈希失败: {}", e))?;
         count += 1;
     }
     Ok(count)
 }
 
-#[tauri::command]
-pub async fn delete_config(id: i64, state: State<'_, AppState>) -> Result<(), String> {
-    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+/// Bytes to write for a config, with the hash they are tracked under: the
+/// hash of the UTF-8 text for text configs, of the bytes for binary ones.
+struct FileOutput {
+    bytes: Vec<u8>,
+    hash: String,
+}
 
-    db::delete_config(&pool, id)
-        .await
-        .map_err(|e| format!("删除配置失败: {}", e))
+impl FileOutput {
+    fn text(config: &Config, content: &str) -> Result<Self, String> {
+        Ok(FileOutput {
+            bytes: encoding::encode(content, &config.encoding).map_err(|e| format!("编码失败: {}", e))?,
+            hash: file_system::calculate_content_hash(content),
+        })
+    }
+
+    fn binary(bytes: Vec<u8>) -> Self {
+        FileOutput {
+            hash: file_system::calculate_bytes_hash(&bytes),
+            bytes,
+        }
+    }
 }
 
-#[tauri::command]
+async fn direct_output(pool: &SqlitePool, config: &Config) -> Result<FileOutput, String> {
+    match config.kind {
+        ConfigKind::Text => FileOutput::text(config, &config.original_content),
+        ConfigKind::Binary => db::get_binary_content(pool, config.id, RevisionField::Original)
+            .await
+            .map_err(|e| format!("读取二进制内容失败: {}", e))?
+            .map(FileOutput::binary)
+            .ok_or_else(|| "二进制内容不存在".to_string()),
+    }
+}
+
+/// Sanitized output for a config. Binary configs are never sanitized; they
+/// only have output when the user supplied a replacement file.
+async fn sanitized_output(pool: &SqlitePool, config: &Config, profile: Option<&str>) -> Result<Option<FileOutput>, String> {
+    match config.kind {
+        ConfigKind::Text => {
+            let content = render_sanitized(pool, config, profile).await?;
+            FileOutput::text(config, &content).map(Some)
+        }
+        ConfigKind::Binary => Ok(db::get_binary_content(pool, config.id, RevisionField::Sanitized)
+            .await
+            .map_err(|e| format!("读取替换文件失败: {}", e))?
+            .map(FileOutput::binary)),
+    }
+}
+
+/// Hash of a config's file on disk, comparable with its stored hashes;
+/// `None` when the file is missing.
+fn disk_hash(
+    workspace_root: &str,
+    path: &str,
+    kind: ConfigKind,
+    policy: SymlinkPolicy,
+) -> Result<Option<String>, FileSystemError> {
+    let hash = match kind {
+        ConfigKind::Text => file_system::calculate_file_hash(workspace_root, path, policy),
+        ConfigKind::Binary => file_system::read_file_bytes(workspace_root, path, policy)
+            .map(|content| file_system::calculate_bytes_hash(&content)),
+    };
+    match hash {
+        Ok(hash) => Ok(Some(hash)),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Checks that none of the target files changed on disk since they were last
+/// synced. A file is safe to replace when it is missing, already holds the new
+/// content, matches the expected hash (by default the last-synced hash) or is
+/// a sanitized output this app wrote. Configs synced before hashes were
+/// recorded are checked against their original content.
+///
+/// Returns `Some("forced")` as audit detail when `force` overrode a conflict.
+/// A refused write is audited as a failed `action` for each conflicting file.
+async fn guard_writes(
+    pool: &SqlitePool,
+    writes: &[(&Config, &FileOutput)],
+    workspace_root: &str,
+    expected_hash: Option<&str>,
+    force: Option<bool>,
+    action: AuditAction,
+) -> Result<Option<String>, WriteError> {
+    let mut conflicts = Vec::new();
+    for (config, output) in writes {
+        let policy = symlink_policy(pool, config.workspace_id).await?;
+        let current_hash = disk_hash(workspace_root, &config.path, config.kind, policy)
+            .map_err(|e| format!("读取文件 {} 失败: {}", config.path, e))?;
+        let Some(current_hash) = current_hash.filter(|hash| *hash != output.hash) else {
+            continue;
+        };
+
+        let hashes = db::get_config_hashes_by_id(pool, config.id)
+            .await
+            .map_err(|e| format!("获取配置失败: {}", e))?
+            .ok_or("配置不存在")?;
+        // Configs synced before hashes were recorded are held to their original
+        let expected_hash = expected_hash
+            .or(hashes.synced_hash.as_deref())
+            .unwrap_or(&hashes.original_hash);
+        if current_hash == expected_hash {
+            continue;
+        }
+        let is_output = db::is_sanitized_output(pool, config.id, &current_hash)
+            .await
+            .map_err(|e| format!("检查脱敏输出失败: {}", e))?;
+        if is_output {
+            continue;
+        }
+
+        let merge = match config.kind {
+            ConfigKind::Text => {
+                let disk_content = file_system::read_file(workspace_root, &config.path, policy)
+                    .map_err(|e| format!("读取文件 {} 失败: {}", config.path, e))?;
+                Some(merge_data(pool, config, disk_content).await?)
+            }
+            ConfigKind::Binary => None,
+        };
+        conflicts.push(WriteConflict {
+            config_id: config.id,
+            path: config.path.clone(),
+            status: hashes.status(Some(&current_hash)),
+            expected_hash: Some(expected_hash.to_string()),
+            current_hash,
+            merge,
+        });
+    }
+
+    if conflicts.is_empty() {
+        return Ok(None);
+    }
+    if force.unwrap_or(false) {
+        return Ok(Some("forced".to_string()));
+    }
+    let paths: Vec<&str> = conflicts.iter().map(|c| c.path.as_str()).collect();
+    let message = format!("文件在磁盘上已被修改，请先合并: {}", paths.join(", "));
+    for conflict in &conflicts {
+        let mut record = AuditRecord::new(action);
+        record.workspace_id = writes.iter().find(|(c, _)| c.id == conflict.config_id).map(|(c, _)| c.workspace_id);
+        record.config_id = Some(conflict.config_id);
+        record.target_path = Some(Path::new(workspace_root).join(&conflict.path).display().to_string());
+        record.hash_before = Some(conflict.current_hash.clone());
+        audit(pool, record.with_result::<()>(&Err("refused: changed on disk".to_string()))).await?;
+    }
+    Err(WriteError::Conflict { message, conflicts })
+}
+
+fn write_detail(profile: Option<&str>, forced: Option<String>) -> Option<String> {
+    match (profile, forced) {
+        (Some(profile), Some(forced)) => Some(format!("{}, {}", profile, forced)),
+        (profile, forced) => profile.map(str::to_string).or(forced),
+    }
+}
+
+/// Takes the file currently on disk as a binary config's original content.
+/// Binary files cannot be merged, so this is how external changes are accepted.
+#[tauri::command]
+pub async fn pull_binary_config(id: i64, workspace_root: String, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = require_binary(&pool, id).await?;
+    let policy = symlink_policy(&pool, config.workspace_id).await?;
+    let content = file_system::read_file_bytes(&workspace_root, &config.path, policy)
+        .map_err(|e| format!("读取文件失败: {}", e))?;
+    let hash_before = db::get_config_hashes_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .map(|hashes| hashes.original_hash);
+
+    let result = async {
+        db::set_binary_content(&pool, id, RevisionField::Original, Some(&content))
+            .await
+            .map_err(|e| format!("更新内容失败: {}", e))?;
+        db::update_synced_bytes(&pool, id, &content)
+            .await
+            .map_err(|e| format!("更新同步基线失败: {}", e))
+    }
+    .await;
+
+    let mut record = AuditRecord::new(AuditAction::ResolveConflict);
+    record.workspace_id = Some(config.workspace_id);
+    record.config_id = Some(id);
+    record.target_path = Some(config.path.clone());
+    record.hash_before = hash_before;
+    record.hash_after = Some(file_system::calculate_bytes_hash(&content));
+    record.detail = Some("binary".to_string());
+    audit(&pool, record.with_result(&result)).await?;
+    result
+}
+
+/// Sets the file a binary config is replaced with by sanitized writes, read
+/// from `source_path`. `None` removes it, so sanitized writes skip the config.
+#[tauri::command]
+pub async fn set_binary_replacement(
+    id: i64,
+    source_path: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    require_binary(&pool, id).await?;
+    let content = match source_path {
+        Some(path) => Some(std::fs::read(&path).map_err(|e| format!("读取替换文件失败: {}", e))?),
+        None => None,
+    };
+
+    db::set_binary_content(&pool, id, RevisionField::Sanitized, content.as_deref())
+        .await
+        .map_err(|e| format!("更新替换文件失败: {}", e))
+}
+
+async fn require_binary(pool: &SqlitePool, id: i64) -> Result<Config, String> {
+    let config = db::get_config_by_id(pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    match config.kind {
+        ConfigKind::Binary => Ok(config),
+        ConfigKind::Text => Err("该配置不是二进制文件".to_string()),
+    }
+}
+
+/// Text-only operations (editing, sanitizing, diffing, merging) refuse
+/// binary configs.
+fn require_text(config: &Config) -> Result<(), String> {
+    match config.kind {
+        ConfigKind::Text => Ok(()),
+        ConfigKind::Binary => Err("二进制配置不支持该操作".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn delete_config(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?;
+    // The stored hash covers binary configs, whose content is not loaded as text
+    let hashes = db::get_config_hashes_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?;
+
+    let result = db::delete_config(&pool, id)
+        .await
+        .map_err(|e| format!("删除配置失败: {}", e));
+
+    let mut record = AuditRecord::new(AuditAction::DeleteConfig);
+    record.config_id = Some(id);
+    record.hash_before = hashes.map(|hashes| hashes.original_hash);
+    if let Some(config) = config {
+        rewatch(&state, &pool, config.workspace_id).await;
+        record.workspace_id = Some(config.workspace_id);
+        record.target_path = Some(config.path);
+    }
+    audit(&pool, record.with_result(&result)).await?;
+    result
+}
+
+#[tauri::command]
 pub async fn get_sanitized_preview(
     id: i64,
+    profile: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
     let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
@@ -289,16 +1172,1195 @@ pub async fn get_sanitized_preview(
         .map_err(|e| format!("获取配置失败: {}", e))?
         .ok_or("配置不存在")?;
 
-    if let Some(manual_content) = config.sanitized_content {
-        return Ok(manual_content);
-    }
+    render_sanitized(&pool, &config, profile.as_deref()).await
+}
+
+/// Produces the sanitized variant of a config. Without a profile the config's
+/// manual copy wins over automatic sanitization; with one, that profile's
+/// manual override wins over sanitizing with the profile's rules. Per-key
+/// overrides are applied on top of automatic sanitization in both cases.
+async fn render_sanitized(pool: &SqlitePool, config: &Config, profile: Option<&str>) -> Result<String, String> {
+    require_text(config)?;
+    // Without an explicit profile or manual content, a set member uses its set's profile
+    let set_profile = match (profile, &config.sanitized_content) {
+        (None, None) => config_set_for(pool, config).await?.and_then(|set| set.profile),
+        _ => None,
+    };
+    let overrides: HashMap<String, String> = db::get_key_overrides(pool, config.id)
+        .await
+        .map_err(|e| format!("获取键值覆盖失败: {}", e))?
+        .into_iter()
+        .map(|o| (o.key_path, o.value))
+        .collect();
+
+    let mut profile_rules = Vec::new();
+    let mut policy = match profile.or(set_profile.as_deref()) {
+        Some(name) => {
+            let profile = db::get_profile_by_name(pool, name)
+                .await
+                .map_err(|e| format!("获取脱敏配置方案失败: {}", e))?
+                .ok_or_else(|| format!("脱敏配置方案不存在: {}", name))?;
+
+            let manual_content = db::get_profile_override(pool, config.id, profile.id)
+                .await
+                .map_err(|e| format!("获取手动脱敏内容失败: {}", e))?;
+            if let Some(manual_content) = manual_content {
+                return apply_overrides(pool, config, &manual_content, overrides).await;
+            }
+
+            profile_rules = profile.line_rules;
+            MaskPolicy {
+                reveal_keys: profile.reveal_keys,
+                mask_keys: profile.mask_keys,
+                placeholder: profile.placeholder,
+                ..MaskPolicy::default()
+            }
+        }
+        None => {
+            if let Some(ref manual_content) = config.sanitized_content {
+                return apply_overrides(pool, config, manual_content, overrides).await;
+            }
+            MaskPolicy::default()
+        }
+    };
+    policy.overrides = overrides;
 
-    match sanitizer::sanitize_content(&config.original_content, &config.path) {
+    let registry = load_registry(pool, config.workspace_id, Some(&config.path), &profile_rules).await?;
+    match sanitizer::sanitize_content(&config.original_content, &config.path, &registry, &policy) {
         Ok(sanitized) => Ok(sanitized),
+        // 原样返回会泄露原始内容
         Err(crate::sanitizer::SanitizerError::UnsupportedFormat(_)) => {
-            // 不支持自动脱敏的格式，返回原始内容
-            Ok(config.original_content)
+            Err("该格式不支持自动脱敏，请添加行规则或手动脱敏内容".to_string())
         }
         Err(e) => Err(format!("脱敏失败: {}", e)),
     }
 }
+
+/// Applies per-key overrides to manually sanitized content, leaving every
+/// other value as written.
+async fn apply_overrides(
+    pool: &SqlitePool,
+    config: &Config,
+    manual_content: &str,
+    overrides: HashMap<String, String>,
+) -> Result<String, String> {
+    if overrides.is_empty() {
+        return Ok(manual_content.to_string());
+    }
+    let registry = load_registry(pool, config.workspace_id, Some(&config.path), &[]).await?;
+    sanitizer::sanitize_content(manual_content, &config.path, &registry, &MaskPolicy::overrides_only(overrides))
+        .map_err(|e| format!("手动脱敏内容无法应用键值覆盖: {}", e))
+}
+
+// --- Sanitizer registry ---
+
+/// Compiled workspace scripts, shared by every render.
+static SCRIPT_CACHE: LazyLock<scripting::ScriptCache> = LazyLock::new(scripting::ScriptCache::default);
+
+/// Builds a workspace's sanitizer registry: workspace scripts first, then
+/// built-in formats and its line rules plus `extra_rules` (a profile's) as
+/// the fallback, reordered by the workspace's format order, with its format
+/// overrides. With `file_path`, only the line rules for that file are
+/// included; without, all of them, e.g. to list what can be selected.
+async fn load_registry(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    file_path: Option<&str>,
+    extra_rules: &[String],
+) -> Result<SanitizerRegistry, String> {
+    let mut registry = SanitizerRegistry::with_builtins();
+
+    let scripts = db::get_sanitizer_scripts_by_workspace(pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取脱敏脚本失败: {}", e))?;
+    // Scripts added before names were checked may still shadow a builtin
+    if let Some(script) = scripts.iter().find(|s| sanitizer::is_builtin_format(&s.name)) {
+        return Err(format!("脱敏脚本 {} 与内置格式同名，请删除后重新添加", script.name));
+    }
+    let compiled = SCRIPT_CACHE
+        .load(workspace_id, &scripts)
+        .map_err(|e| format!("脱敏脚本无效: {}", e))?;
+    for sanitizer in compiled.into_iter().rev() {
+        registry.register_first(Box::new(sanitizer));
+    }
+
+    let rules = db::get_line_rules_by_workspace(pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取脱敏规则失败: {}", e))?;
+    let patterns: Vec<String> = rules
+        .into_iter()
+        .filter(|rule| match (rule.path_glob.as_deref(), file_path) {
+            (Some(glob), Some(path)) => file_system::path_matches_glob(glob, path),
+            _ => true,
+        })
+        .map(|rule| rule.pattern)
+        .chain(extra_rules.iter().cloned())
+        .collect();
+    if !patterns.is_empty() {
+        let line_rules = sanitizer::LineRuleSanitizer::new(&patterns)
+            .map_err(|e| format!("脱敏规则无效: {}", e))?;
+        registry.register(Box::new(line_rules));
+    }
+
+    let order = db::get_format_order(pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取格式顺序失败: {}", e))?;
+    registry.reorder(&order);
+
+    let overrides = db::get_format_overrides_by_workspace(pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取格式覆盖失败: {}", e))?;
+    for item in &overrides {
+        registry
+            .add_override(&item.path_glob, &item.format)
+            .map_err(|e| format!("格式覆盖无效: {}", e))?;
+    }
+
+    Ok(registry)
+}
+
+/// The workspace's formats in detection order: its scripts, the builtins and
+/// its line rules, as reordered by `set_format_order`.
+#[tauri::command]
+pub async fn get_available_formats(workspace_id: i64, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let registry = load_registry(&pool, workspace_id, None, &[]).await?;
+    Ok(registry.names().into_iter().map(String::from).collect())
+}
+
+/// Sets the order in which a workspace's formats are tried on files without
+/// a format override. Formats not listed follow in their default order.
+#[tauri::command]
+pub async fn set_format_order(
+    workspace_id: i64,
+    formats: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let registry = load_registry(&pool, workspace_id, None, &[]).await?;
+    let mut seen = HashSet::new();
+    for format in &formats {
+        if registry.get(format).is_none() {
+            return Err(format!("未知格式: {}", format));
+        }
+        if !seen.insert(format) {
+            return Err(format!("格式重复: {}", format));
+        }
+    }
+
+    db::set_format_order(&pool, workspace_id, &formats)
+        .await
+        .map_err(|e| format!("更新格式顺序失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn add_format_override(
+    workspace_id: i64,
+    path_glob: String,
+    format: String,
+    priority: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    glob::Pattern::new(&path_glob).map_err(|e| format!("路径通配符无效: {}", e))?;
+    load_registry(&pool, workspace_id, None, &[])
+        .await?
+        .add_override(&path_glob, &format)
+        .map_err(|e| format!("格式覆盖无效: {}", e))?;
+
+    db::add_format_override(&pool, workspace_id, &path_glob, &format, priority.unwrap_or(0))
+        .await
+        .map_err(|e| format!("添加格式覆盖失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_format_overrides(
+    workspace_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<FormatOverride>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_format_overrides_by_workspace(&pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取格式覆盖失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_format_override(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let result = db::delete_format_override(&pool, id)
+        .await
+        .map_err(|e| format!("删除格式覆盖失败: {}", e));
+    audit_rule_deletion(&pool, "format_override", id, &result).await?;
+    result
+}
+
+// --- Line rule commands ---
+
+#[tauri::command]
+pub async fn add_line_rule(
+    workspace_id: i64,
+    path_glob: Option<String>,
+    pattern: String,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    sanitizer::compile_line_rules(std::slice::from_ref(&pattern))
+        .map_err(|e| format!("规则无效: {}", e))?;
+
+    if let Some(ref glob) = path_glob {
+        glob::Pattern::new(glob).map_err(|e| format!("路径通配符无效: {}", e))?;
+    }
+
+    db::add_line_rule(&pool, workspace_id, path_glob.as_deref(), &pattern)
+        .await
+        .map_err(|e| format!("添加脱敏规则失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_line_rules(
+    workspace_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<LineRule>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_line_rules_by_workspace(&pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取脱敏规则失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_line_rule(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let result = db::delete_line_rule(&pool, id)
+        .await
+        .map_err(|e| format!("删除脱敏规则失败: {}", e));
+    audit_rule_deletion(&pool, "line_rule", id, &result).await?;
+    result
+}
+
+// --- Sanitizer script commands ---
+
+#[tauri::command]
+pub async fn add_sanitizer_script(
+    workspace_id: i64,
+    name: String,
+    path_glob: String,
+    source: String,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    glob::Pattern::new(&path_glob).map_err(|e| format!("路径通配符无效: {}", e))?;
+    if sanitizer::is_builtin_format(&name) {
+        return Err(format!("脚本名称不能与内置格式 {} 相同", name));
+    }
+    scripting::ScriptSanitizer::new(&name, &path_glob, &source)
+        .map_err(|e| format!("脱敏脚本无效: {}", e))?;
+
+    db::add_sanitizer_script(&pool, workspace_id, &name, &path_glob, &source)
+        .await
+        .map_err(|e| format!("添加脱敏脚本失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_sanitizer_scripts(
+    workspace_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<SanitizerScript>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_sanitizer_scripts_by_workspace(&pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取脱敏脚本失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_sanitizer_script(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let result = db::delete_sanitizer_script(&pool, id)
+        .await
+        .map_err(|e| format!("删除脱敏脚本失败: {}", e));
+    audit_rule_deletion(&pool, "sanitizer_script", id, &result).await?;
+    result
+}
+
+// --- Config set commands ---
+
+/// Registers a glob whose matching files are tracked as configs, and imports
+/// the files that already match.
+#[tauri::command]
+pub async fn add_config_set(
+    workspace_id: i64,
+    workspace_root: String,
+    pattern: String,
+    profile: Option<String>,
+    write_mode: WriteMode,
+    state: State<'_, AppState>,
+) -> Result<ConfigSetSync, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    glob::Pattern::new(&pattern).map_err(|e| format!("路径通配符无效: {}", e))?;
+    if let Some(ref name) = profile {
+        db::get_profile_by_name(&pool, name)
+            .await
+            .map_err(|e| format!("获取脱敏配置方案失败: {}", e))?
+            .ok_or_else(|| format!("脱敏配置方案不存在: {}", name))?;
+    }
+
+    let id = db::add_config_set(&pool, workspace_id, &pattern, profile.as_deref(), write_mode)
+        .await
+        .map_err(|e| format!("添加配置集失败: {}", e))?;
+    let set = require_config_set(&pool, id).await?;
+    let sync = sync_config_set(&pool, &set, &workspace_root).await?;
+    rewatch(&state, &pool, workspace_id).await;
+    Ok(sync)
+}
+
+#[tauri::command]
+pub async fn get_config_sets(workspace_id: i64, state: State<'_, AppState>) -> Result<Vec<ConfigSet>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_config_sets_by_workspace(&pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取配置集失败: {}", e))
+}
+
+/// Removes a set. Its members stay tracked as individual configs.
+#[tauri::command]
+pub async fn delete_config_set(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let set = require_config_set(&pool, id).await?;
+    let result = db::delete_config_set(&pool, id)
+        .await
+        .map_err(|e| format!("删除配置集失败: {}", e));
+    audit_rule_deletion(&pool, "config_set", id, &result).await?;
+    rewatch(&state, &pool, set.workspace_id).await;
+    result
+}
+
+/// Matches every set of the workspace against the files on disk.
+#[tauri::command]
+pub async fn sync_config_sets(
+    workspace_id: i64,
+    workspace_root: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<ConfigSetSync>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let synced = sync_workspace_sets(&pool, workspace_id, &workspace_root).await?;
+    rewatch(&state, &pool, workspace_id).await;
+    Ok(synced)
+}
+
+/// Writes every member of a set in the set's write mode, checking all of
+/// them for conflicts first. Binary members without a replacement file are
+/// skipped by sanitized writes.
+#[tauri::command]
+pub async fn write_config_set(
+    id: i64,
+    workspace_root: String,
+    force: Option<bool>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<usize, WriteError> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let set = require_config_set(&pool, id).await?;
+    let members = set_members(&pool, &set).await?;
+
+    let mut writes = Vec::with_capacity(members.len());
+    for config in &members {
+        let output = match set.write_mode {
+            WriteMode::Direct => Some(direct_output(&pool, config).await?),
+            WriteMode::Sanitized => sanitized_output(&pool, config, set.profile.as_deref())
+                .await
+                .map_err(|e| format!("{} ({})", e, config.path))?,
+        };
+        if let Some(output) = output {
+            writes.push((config, output));
+        }
+    }
+    let targets: Vec<(&Config, &FileOutput)> = writes.iter().map(|(c, o)| (*c, o)).collect();
+    let action = match set.write_mode {
+        WriteMode::Direct => AuditAction::WriteDirect,
+        WriteMode::Sanitized => AuditAction::WriteSanitized,
+    };
+    let forced = guard_writes(&pool, &targets, &workspace_root, None, force, action).await?;
+    let detail = match set.write_mode {
+        WriteMode::Direct => forced,
+        WriteMode::Sanitized => write_detail(set.profile.as_deref(), forced),
+    };
+
+    let mut count = 0;
+    for (config, output) in targets {
+        write_config_file(&app, &pool, config, &workspace_root, &output.bytes, action, detail.clone())
+            .await
+            .map_err(|e| format!("写入文件 {} 失败: {}", config.path, e))?;
+        match set.write_mode {
+            WriteMode::Direct => db::update_synced_hash(&pool, config.id, &output.hash)
+                .await
+                .map_err(|e| format!("更新同步基线失败: {}", e))?,
+            WriteMode::Sanitized => db::record_sanitized_output(&pool, config.id, &output.hash, set.profile.as_deref())
+                .await
+                .map_err(|e| format!("更新脱敏输出哈希失败: {}", e))?,
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Syncs every set of a workspace; also run by the watcher when files
+/// matching a set appear or disappear.
+pub async fn sync_workspace_sets(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    workspace_root: &str,
+) -> Result<Vec<ConfigSetSync>, String> {
+    let sets = db::get_config_sets_by_workspace(pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取配置集失败: {}", e))?;
+
+    let mut results = Vec::with_capacity(sets.len());
+    for set in &sets {
+        results.push(sync_config_set(pool, set, workspace_root).await?);
+    }
+    Ok(results)
+}
+
+/// Imports the files newly matching a set and reports members whose file is
+/// gone. Unlike `import_configs`, a file that cannot be imported is skipped
+/// rather than failing the others.
+async fn sync_config_set(pool: &SqlitePool, set: &ConfigSet, workspace_root: &str) -> Result<ConfigSetSync, String> {
+    let files = discovery::matching_files(workspace_root, &set.pattern)
+        .map_err(|e| format!("扫描工作区失败: {}", e))?;
+    let tracked = tracked_paths(pool, set.workspace_id).await?;
+    let policy = symlink_policy(pool, set.workspace_id).await?;
+
+    let mut imports = Vec::new();
+    let mut skipped = Vec::new();
+    for path in files.iter().filter(|path| !tracked.contains(*path)) {
+        let Ok(bytes) = file_system::read_file_bytes(workspace_root, path, policy) else {
+            skipped.push(path.clone());
+            continue;
+        };
+        imports.push(imported_file(set.workspace_id, path.clone(), bytes));
+    }
+
+    let added = db::import_configs(pool, &imports)
+        .await
+        .map_err(|e| format!("导入配置失败: {}", e))?;
+    let missing = set_member_summaries(pool, set)
+        .await?
+        .into_iter()
+        .filter(|config| files.binary_search(&config.path).is_err())
+        .map(|config| config.id)
+        .collect();
+
+    Ok(ConfigSetSync { set_id: set.id, added, missing, skipped })
+}
+
+async fn require_config_set(pool: &SqlitePool, id: i64) -> Result<ConfigSet, String> {
+    db::get_config_set(pool, id)
+        .await
+        .map_err(|e| format!("获取配置集失败: {}", e))?
+        .ok_or_else(|| "配置集不存在".to_string())
+}
+
+/// Configs the set owns, without their contents: those matching its pattern
+/// that no more specific set claims.
+async fn set_member_summaries(pool: &SqlitePool, set: &ConfigSet) -> Result<Vec<ConfigSummary>, String> {
+    let summaries = db::get_config_summaries(pool, set.workspace_id)
+        .await
+        .map_err(|e| format!("获取配置列表失败: {}", e))?;
+    let sets = db::get_config_sets_by_workspace(pool, set.workspace_id)
+        .await
+        .map_err(|e| format!("获取配置集失败: {}", e))?;
+    Ok(summaries
+        .into_iter()
+        .filter(|config| discovery::owning_set(&sets, &config.path).is_some_and(|owner| owner.id == set.id))
+        .collect())
+}
+
+async fn set_members(pool: &SqlitePool, set: &ConfigSet) -> Result<Vec<Config>, String> {
+    let mut members = Vec::new();
+    for summary in set_member_summaries(pool, set).await? {
+        let config = db::get_config_by_id(pool, summary.id)
+            .await
+            .map_err(|e| format!("获取配置失败: {}", e))?;
+        members.extend(config);
+    }
+    Ok(members)
+}
+
+/// The set owning the config's path, the most specific one when several
+/// patterns match.
+async fn config_set_for(pool: &SqlitePool, config: &Config) -> Result<Option<ConfigSet>, String> {
+    let sets = db::get_config_sets_by_workspace(pool, config.workspace_id)
+        .await
+        .map_err(|e| format!("获取配置集失败: {}", e))?;
+    Ok(discovery::owning_set(&sets, &config.path).cloned())
+}
+
+// --- Sanitize profile commands ---
+
+#[tauri::command]
+pub async fn create_profile(
+    name: String,
+    reveal_keys: Vec<String>,
+    mask_keys: Option<Vec<String>>,
+    line_rules: Option<Vec<String>>,
+    placeholder: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let mask_keys = mask_keys.unwrap_or_default();
+    let line_rules = line_rules.unwrap_or_default();
+    validate_profile_rules(&reveal_keys, &mask_keys, &line_rules)?;
+    let placeholder = placeholder.unwrap_or_else(|| MaskPolicy::default().placeholder);
+
+    db::add_profile(&pool, &name, &reveal_keys, &mask_keys, &line_rules, &placeholder)
+        .await
+        .map_err(|e| format!("添加脱敏配置方案失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_profiles(state: State<'_, AppState>) -> Result<Vec<SanitizeProfile>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_all_profiles(&pool)
+        .await
+        .map_err(|e| format!("获取脱敏配置方案失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn update_profile(
+    id: i64,
+    name: String,
+    reveal_keys: Vec<String>,
+    mask_keys: Vec<String>,
+    line_rules: Vec<String>,
+    placeholder: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    validate_profile_rules(&reveal_keys, &mask_keys, &line_rules)?;
+
+    db::update_profile(&pool, id, &name, &reveal_keys, &mask_keys, &line_rules, &placeholder)
+        .await
+        .map_err(|e| format!("更新脱敏配置方案失败: {}", e))
+}
+
+fn validate_profile_rules(reveal_keys: &[String], mask_keys: &[String], line_rules: &[String]) -> Result<(), String> {
+    for pattern in reveal_keys.iter().chain(mask_keys) {
+        glob::Pattern::new(pattern).map_err(|e| format!("键路径通配符无效: {}", e))?;
+    }
+    sanitizer::compile_line_rules(line_rules).map_err(|e| format!("脱敏规则无效: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_profile(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let result = db::delete_profile(&pool, id)
+        .await
+        .map_err(|e| format!("删除脱敏配置方案失败: {}", e));
+    audit_rule_deletion(&pool, "profile", id, &result).await?;
+    result
+}
+
+async fn require_profile(pool: &SqlitePool, name: &str) -> Result<SanitizeProfile, String> {
+    db::get_profile_by_name(pool, name)
+        .await
+        .map_err(|e| format!("获取脱敏配置方案失败: {}", e))?
+        .ok_or_else(|| format!("脱敏配置方案不存在: {}", name))
+}
+
+#[tauri::command]
+pub async fn get_profile_override(
+    config_id: i64,
+    profile: String,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let profile = require_profile(&pool, &profile).await?;
+    db::get_profile_override(&pool, config_id, profile.id)
+        .await
+        .map_err(|e| format!("获取手动脱敏内容失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn set_profile_override(
+    config_id: i64,
+    profile: String,
+    content: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let profile = require_profile(&pool, &profile).await?;
+    db::set_profile_override(&pool, config_id, profile.id, content.as_deref())
+        .await
+        .map_err(|e| format!("更新手动脱敏内容失败: {}", e))
+}
+
+// --- Key override commands ---
+
+#[tauri::command]
+pub async fn get_config_entries(id: i64, state: State<'_, AppState>) -> Result<Vec<SanitizerEntry>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = db::get_config_by_id(&pool, id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    require_text(&config)?;
+
+    let registry = load_registry(&pool, config.workspace_id, Some(&config.path), &[]).await?;
+    registry
+        .entries(&config.original_content, &config.path)
+        .map_err(|e| format!("解析配置失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_key_overrides(config_id: i64, state: State<'_, AppState>) -> Result<Vec<KeyOverride>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_key_overrides(&pool, config_id)
+        .await
+        .map_err(|e| format!("获取键值覆盖失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn set_key_override(
+    config_id: i64,
+    key_path: String,
+    value: String,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let config = db::get_config_by_id(&pool, config_id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    require_text(&config)?;
+    let registry = load_registry(&pool, config.workspace_id, Some(&config.path), &[]).await?;
+    let entries = registry
+        .entries(&config.original_content, &config.path)
+        .map_err(|e| format!("解析配置失败: {}", e))?;
+    if !entries.iter().any(|entry| entry.key == key_path) {
+        return Err(format!("键路径不存在或不是单个值: {}", key_path));
+    }
+
+    db::set_key_override(&pool, config_id, &key_path, &value)
+        .await
+        .map_err(|e| format!("设置键值覆盖失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_key_override(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let result = db::delete_key_override(&pool, id)
+        .await
+        .map_err(|e| format!("删除键值覆盖失败: {}", e));
+    audit_rule_deletion(&pool, "key_override", id, &result).await?;
+    result
+}
+
+// --- Diff commands ---
+
+/// Loads the content of one diff side along with a label for patch headers.
+async fn load_diff_source(pool: &SqlitePool, source: &DiffSource) -> Result<(String, String), String> {
+    let (config_id, revision) = match source {
+        DiffSource::Db { config_id }
+        | DiffSource::Disk { config_id, .. }
+        | DiffSource::Sanitized { config_id, .. } => (*config_id, None),
+        DiffSource::Revision { revision_id } => {
+            let revision = require_revision(pool, *revision_id).await?;
+            (revision.config_id, Some(revision))
+        }
+    };
+
+    let config = db::get_config_by_id(pool, config_id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+    require_text(&config)?;
+
+    match source {
+        DiffSource::Db { .. } => Ok((format!("db/{}", config.path), config.original_content)),
+        DiffSource::Disk { workspace_root, .. } => {
+            let policy = symlink_policy(pool, config.workspace_id).await?;
+            let content = file_system::read_file(workspace_root, &config.path, policy)
+                .map_err(|e| format!("读取文件失败: {}", e))?;
+            Ok((format!("disk/{}", config.path), content))
+        }
+        DiffSource::Sanitized { profile, .. } => {
+            let content = render_sanitized(pool, &config, profile.as_deref()).await?;
+            let label = match profile {
+                Some(name) => format!("sanitized-{}/{}", name, config.path),
+                None => format!("sanitized/{}", config.path),
+            };
+            Ok((label, content))
+        }
+        DiffSource::Revision { .. } => {
+            let revision = revision.ok_or("历史版本不存在")?;
+            let label = format!("revision-{}/{}", revision.id, config.path);
+            Ok((label, revision.content.unwrap_or_default()))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn diff_configs(
+    left: DiffSource,
+    right: DiffSource,
+    context: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<ConfigDiff, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let (left_label, left_content) = load_diff_source(&pool, &left).await?;
+    let (right_label, right_content) = load_diff_source(&pool, &right).await?;
+
+    Ok(ConfigDiff {
+        diff: diff::diff_text(&left_content, &right_content, context.unwrap_or(diff::DEFAULT_CONTEXT)),
+        left_label,
+        right_label,
+    })
+}
+
+#[tauri::command]
+pub async fn export_diff_patch(
+    left: DiffSource,
+    right: DiffSource,
+    context: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let (left_label, left_content) = load_diff_source(&pool, &left).await?;
+    let (right_label, right_content) = load_diff_source(&pool, &right).await?;
+
+    Ok(diff::unified_patch(
+        &left_content,
+        &right_content,
+        &left_label,
+        &right_label,
+        context.unwrap_or(diff::DEFAULT_CONTEXT),
+    ))
+}
+
+// --- Revision commands ---
+
+async fn require_revision(pool: &SqlitePool, id: i64) -> Result<ConfigRevision, String> {
+    db::get_revision(pool, id)
+        .await
+        .map_err(|e| format!("获取历史版本失败: {}", e))?
+        .ok_or_else(|| "历史版本不存在".to_string())
+}
+
+#[tauri::command]
+pub async fn get_revisions(config_id: i64, state: State<'_, AppState>) -> Result<Vec<RevisionSummary>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_revisions(&pool, config_id)
+        .await
+        .map_err(|e| format!("获取历史版本失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_revision(id: i64, state: State<'_, AppState>) -> Result<ConfigRevision, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    require_revision(&pool, id).await
+}
+
+/// Diffs a revision against the config's current content for the same field.
+#[tauri::command]
+pub async fn diff_revision(
+    id: i64,
+    context: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<ConfigDiff, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let revision = require_revision(&pool, id).await?;
+    let config = db::get_config_by_id(&pool, revision.config_id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+
+    let current = match revision.field {
+        RevisionField::Original => config.original_content,
+        RevisionField::Sanitized => config.sanitized_content.unwrap_or_default(),
+    };
+    let revision_content = revision.content.unwrap_or_default();
+
+    Ok(ConfigDiff {
+        left_label: format!("revision-{}/{}", revision.id, config.path),
+        right_label: format!("current/{}", config.path),
+        diff: diff::diff_text(&revision_content, &current, context.unwrap_or(diff::DEFAULT_CONTEXT)),
+    })
+}
+
+/// Restores a revision as the config's current content. The restore is itself
+/// recorded, so it can be undone the same way.
+#[tauri::command]
+pub async fn restore_revision(id: i64, state: State<'_, AppState>) -> Result<Config, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let revision = require_revision(&pool, id).await?;
+    let config = db::get_config_by_id(&pool, revision.config_id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or("配置不存在")?;
+
+    save_content(&pool, &config, revision.field, revision.content.as_deref(), RevisionSource::Restore).await?;
+
+    db::get_config_by_id(&pool, config.id)
+        .await
+        .map_err(|e| format!("获取配置失败: {}", e))?
+        .ok_or_else(|| "配置不存在".to_string())
+}
+
+// --- Snapshot commands ---
+
+#[tauri::command]
+pub async fn create_snapshot(workspace_id: i64, name: String, state: State<'_, AppState>) -> Result<i64, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::create_snapshot(&pool, workspace_id, &name)
+        .await
+        .map_err(|e| format!("创建快照失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_snapshots(workspace_id: i64, state: State<'_, AppState>) -> Result<Vec<WorkspaceSnapshot>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_snapshots(&pool, workspace_id)
+        .await
+        .map_err(|e| format!("获取快照失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_snapshot(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let snapshot = db::get_snapshot(&pool, id)
+        .await
+        .map_err(|e| format!("获取快照失败: {}", e))?;
+
+    let result = db::delete_snapshot(&pool, id)
+        .await
+        .map_err(|e| format!("删除快照失败: {}", e));
+
+    let mut record = AuditRecord::new(AuditAction::DeleteSnapshot);
+    if let Some(snapshot) = snapshot {
+        record.workspace_id = Some(snapshot.workspace_id);
+        record.detail = Some(snapshot.name);
+    }
+    audit(&pool, record.with_result(&result)).await?;
+    result
+}
+
+async fn require_snapshot(pool: &SqlitePool, id: i64) -> Result<WorkspaceSnapshot, String> {
+    db::get_snapshot(pool, id)
+        .await
+        .map_err(|e| format!("获取快照失败: {}", e))?
+        .ok_or_else(|| "快照不存在".to_string())
+}
+
+/// Diffs every config of the snapshot's workspace against the snapshot,
+/// including configs added or deleted since it was taken.
+#[tauri::command]
+pub async fn diff_snapshot(
+    id: i64,
+    context: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<SnapshotConfigDiff>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+    let context = context.unwrap_or(diff::DEFAULT_CONTEXT);
+
+    let snapshot = require_snapshot(&pool, id).await?;
+    let entries = db::get_snapshot_entries(&pool, id)
+        .await
+        .map_err(|e| format!("获取快照失败: {}", e))?;
+    let configs = db::get_configs_by_workspace(&pool, snapshot.workspace_id)
+        .await
+        .map_err(|e| format!("获取配置列表失败: {}", e))?;
+
+    let mut current: HashMap<i64, Config> = configs.into_iter().map(|c| (c.id, c)).collect();
+    let mut diffs = Vec::new();
+    for entry in entries {
+        let (path, content, change) = match current.remove(&entry.config_id) {
+            Some(config) => {
+                let unchanged = config.original_content == entry.original_content
+                    && config.sanitized_content == entry.sanitized_content;
+                let change = if unchanged { SnapshotChange::Unchanged } else { SnapshotChange::Modified };
+                (config.path, config.original_content, change)
+            }
+            None => (entry.path, String::new(), SnapshotChange::Removed),
+        };
+        diffs.push(SnapshotConfigDiff {
+            config_id: entry.config_id,
+            path,
+            change,
+            diff: diff::diff_text(&entry.original_content, &content, context),
+        });
+    }
+
+    let mut added: Vec<Config> = current.into_values().collect();
+    added.sort_by(|a, b| a.path.cmp(&b.path));
+    for config in added {
+        diffs.push(SnapshotConfigDiff {
+            config_id: config.id,
+            diff: diff::diff_text("", &config.original_content, context),
+            path: config.path,
+            change: SnapshotChange::Added,
+        });
+    }
+    Ok(diffs)
+}
+
+/// Restores all configs captured in a snapshot in one transaction. When a
+/// deleted config's path is tracked again, nothing is restored until the
+/// user picks how to resolve the returned collisions.
+#[tauri::command]
+pub async fn restore_snapshot(
+    id: i64,
+    on_collision: Option<RestoreCollision>,
+    state: State<'_, AppState>,
+) -> Result<SnapshotRestore, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let snapshot = require_snapshot(&pool, id).await?;
+    let restore = db::restore_snapshot(&pool, &snapshot, on_collision)
+        .await
+        .map_err(|e| format!("恢复快照失败: {}", e))?;
+    rewatch(&state, &pool, snapshot.workspace_id).await;
+    Ok(restore)
+}
+
+// --- Audit commands ---
+
+/// Appends an audit entry. An operation whose entry cannot be recorded is
+/// reported as failed.
+async fn audit(pool: &SqlitePool, record: AuditRecord) -> Result<(), String> {
+    db::append_audit_entry(pool, record)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("记录审计日志失败: {}", e))
+}
+
+/// Writes a config's file after backing up its current content.
+async fn write_config_file(
+    app: &AppHandle,
+    pool: &SqlitePool,
+    config: &Config,
+    workspace_root: &str,
+    bytes: &[u8],
+    action: AuditAction,
+    detail: Option<String>,
+) -> Result<(), String> {
+    let policy = symlink_policy(pool, config.workspace_id).await?;
+    let previous = file_system::read_file_bytes(workspace_root, &config.path, policy).ok();
+    let hash_before = previous.as_deref().map(file_system::calculate_bytes_hash);
+
+    let mut record = AuditRecord::new(action);
+    record.workspace_id = Some(config.workspace_id);
+    record.config_id = Some(config.id);
+    record.hash_before = hash_before;
+    record.detail = detail;
+
+    // Keep what is on disk before replacing it, unless the write changes nothing.
+    // A write abandoned because the backup failed is still on record.
+    if let Some(previous) = previous.as_deref().filter(|p| *p != bytes) {
+        let backup = db::add_backup(pool, config.workspace_id, Some(config.id), workspace_root, &config.path, previous)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("备份文件失败: {}", e));
+        if backup.is_err() {
+            record.target_path = Some(Path::new(workspace_root).join(&config.path).display().to_string());
+            audit(pool, record.with_result(&backup)).await?;
+            return backup;
+        }
+    }
+
+    write_audited(app, pool, record, workspace_root, &config.path, bytes, policy).await
+}
+
+/// Emitted with a message when a file was written but its final audit entry
+/// could not be recorded; the pending entry remains.
+pub const AUDIT_FAILED_EVENT: &str = "audit-failed";
+
+/// Writes `bytes` to a workspace file. The write is recorded as pending before
+/// it happens and completed afterwards.
+async fn write_audited(
+    app: &AppHandle,
+    pool: &SqlitePool,
+    mut record: AuditRecord,
+    workspace_root: &str,
+    path: &str,
+    bytes: &[u8],
+    policy: SymlinkPolicy,
+) -> Result<(), String> {
+    record.target_path = Some(Path::new(workspace_root).join(path).display().to_string());
+    record.hash_after = Some(file_system::calculate_bytes_hash(bytes));
+
+    // No write happens unless it is on record first
+    let pending_id = db::append_audit_entry(pool, record.pending())
+        .await
+        .map_err(|e| format!("记录审计日志失败: {}", e))?;
+
+    let result = file_system::write_file_bytes(workspace_root, path, bytes, policy).map_err(|e| e.to_string());
+    if result.is_err() {
+        record.hash_after = None;
+    }
+
+    // The file is already written; a failed final entry leaves the pending one
+    // and must not make the caller treat the write as failed
+    if let Err(e) = db::append_audit_entry(pool, record.with_result(&result).completing(pending_id)).await {
+        let _ = app.emit(AUDIT_FAILED_EVENT, format!("{} 已写入，但记录审计日志失败: {}", path, e));
+    }
+    result
+}
+
+/// Deleting a rule can unmask values on the next sanitized write, so it is
+/// audited like a write.
+async fn audit_rule_deletion(pool: &SqlitePool, kind: &str, id: i64, result: &Result<(), String>) -> Result<(), String> {
+    let mut record = AuditRecord::new(AuditAction::DeleteRule);
+    record.detail = Some(format!("{} #{}", kind, id));
+    audit(pool, record.with_result(result)).await
+}
+
+#[tauri::command]
+pub async fn get_audit_log(query: Option<AuditQuery>, state: State<'_, AppState>) -> Result<Vec<AuditEntry>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::query_audit_log(&pool, &query.unwrap_or_default())
+        .await
+        .map_err(|e| format!("获取审计日志失败: {}", e))
+}
+
+#[tauri::command]
+pub async fn verify_audit_log(state: State<'_, AppState>) -> Result<AuditVerification, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let entries = db::get_audit_chain(&pool)
+        .await
+        .map_err(|e| format!("获取审计日志失败: {}", e))?;
+    Ok(audit::verify_chain(&entries))
+}
+
+/// Exports the full log as JSON Lines in chain order, so it can be verified
+/// independently of the app.
+#[tauri::command]
+pub async fn export_audit_log(state: State<'_, AppState>) -> Result<String, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let entries = db::get_audit_chain(&pool)
+        .await
+        .map_err(|e| format!("获取审计日志失败: {}", e))?;
+
+    let mut output = String::new();
+    for entry in &entries {
+        let line = serde_json::to_string(entry).map_err(|e| format!("导出审计日志失败: {}", e))?;
+        output.push_str(&line);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+// --- Backup commands ---
+
+#[tauri::command]
+pub async fn get_backups(
+    workspace_id: i64,
+    config_id: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<Vec<FileBackup>, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    db::get_backups(&pool, workspace_id, config_id)
+        .await
+        .map_err(|e| format!("获取备份失败: {}", e))
+}
+
+async fn load_backup(pool: &SqlitePool, id: i64) -> Result<(FileBackup, Vec<u8>), String> {
+    let backup = db::get_backup(pool, id)
+        .await
+        .map_err(|e| format!("获取备份失败: {}", e))?
+        .ok_or("备份不存在")?;
+    let content = db::get_blob_bytes(pool, &backup.content_hash)
+        .await
+        .map_err(|e| format!("读取备份内容失败: {}", e))?
+        .ok_or("备份内容不存在")?;
+    Ok((backup, content))
+}
+
+#[tauri::command]
+pub async fn get_backup_content(id: i64, state: State<'_, AppState>) -> Result<String, String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let (backup, content) = load_backup(&pool, id).await?;
+    // Decode the way the file's config is stored, so a legacy encoding reads
+    // back as text; backups of untracked files fall back to detection
+    let config = match backup.config_id {
+        Some(config_id) => db::get_config_by_id(&pool, config_id)
+            .await
+            .map_err(|e| format!("获取配置失败: {}", e))?,
+        None => None,
+    };
+    Ok(match config {
+        Some(config) => encoding::decode_as(&content, &config.encoding),
+        None => encoding::decode(&content).0,
+    })
+}
+
+/// Writes a backup back to its file. The content being replaced is backed up
+/// first, so a restore can itself be undone.
+#[tauri::command]
+pub async fn restore_backup(id: i64, app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let pool = state.pool.lock().unwrap().clone().ok_or("数据库未初始化")?;
+
+    let (backup, content) = load_backup(&pool, id).await?;
+    // The backup may predate the workspace being removed and added again,
+    // so it is looked up by root rather than by the id recorded with it
+    let workspace = db::get_workspace_by_root(&pool, &backup.workspace_root)
+        .await
+        .map_err(|e| format!("获取工作区失败: {}", e))?
+        .ok_or("工作区不存在")?;
+    let policy = workspace.symlink_policy;
+    let previous = file_system::read_file_bytes(&backup.workspace_root, &backup.path, policy).ok();
+
+    if let Some(previous) = previous.as_deref().filter(|p| *p != content.as_slice()) {
+        db::add_backup(&pool, workspace.id, backup.config_id, &backup.workspace_root, &backup.path, previous)
+            .await
+            .map_err(|e| format!("备份文件失败: {}", e))?;
+    }
+
+    let mut record = AuditRecord::new(AuditAction::RestoreBackup);
+    record.workspace_id = Some(workspace.id);
+    record.config_id = backup.config_id;
+    record.hash_before = previous.as_deref().map(file_system::calculate_bytes_hash);
+    record.detail = Some(format!("backup #{}", backup.id));
+    write_audited(&app, &pool, record, &backup.workspace_root, &backup.path, &content, policy)
+        .await
+        .map_err(|e| format!("写入文件失败: {}", e))
+}
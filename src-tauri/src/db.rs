@@ -1,7 +1,17 @@
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePool};
+use sqlx::{Executor, FromRow, Sqlite};
+use std::collections::HashMap;
 use std::path::Path;
 use std::str::FromStr;
-use crate::models::{Config, CreateConfig, Workspace};
+use crate::{audit, blobs, encoding, file_system};
+use crate::audit::AuditRecord;
+use crate::encoding::{LineEnding, TextEncoding};
+use crate::file_system::SymlinkPolicy;
+use crate::models::{
+    AuditEntry, AuditQuery, Config, ConfigHashes, ConfigSummary, ConfigKind, ConfigSet, FileBackup, ConfigRevision, CreateConfig, FormatOverride, ImportedFile, KeyOverride, LineRule, RevisionField, RevisionSource,
+    RestoreCollision, RevisionSummary, SanitizeProfile, SanitizerScript, SnapshotEntry, SnapshotRestore, Workspace,
+    WorkspaceSnapshot, WriteMode,
+};
 
 pub async fn init_db(app_data_dir: &Path) -> Result<SqlitePool, sqlx::Error> {
     let db_path = app_data_dir.join("config_guardian.db");
@@ -11,12 +21,25 @@ pub async fn init_db(app_data_dir: &Path) -> Result<SqlitePool, sqlx::Error> {
 
     let pool = SqlitePool::connect_with(options).await?;
 
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS blobs (
+            hash TEXT PRIMARY KEY,
+            size INTEGER NOT NULL,
+            data BLOB NOT NULL
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
     sqlx::query(
         r#"
         CREATE TABLE IF NOT EXISTS workspaces (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             name TEXT NOT NULL,
-            root_path TEXT NOT NULL UNIQUE
+            root_path TEXT NOT NULL UNIQUE,
+            symlink_policy TEXT NOT NULL DEFAULT 'follow'
         )
         "#,
     )
@@ -32,6 +55,171 @@ pub async fn init_db(app_data_dir: &Path) -> Result<SqlitePool, sqlx::Error> {
             path TEXT NOT NULL,
             original_content TEXT NOT NULL,
             sanitized_content TEXT,
+            synced_content TEXT,
+            original_hash TEXT,
+            sanitized_hash TEXT,
+            synced_hash TEXT,
+            sanitized_output_hash TEXT,
+            encoding TEXT NOT NULL DEFAULT 'UTF-8',
+            has_bom INTEGER NOT NULL DEFAULT 0,
+            line_ending TEXT NOT NULL DEFAULT 'lf',
+            kind TEXT NOT NULL DEFAULT 'text',
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS line_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            workspace_id INTEGER NOT NULL,
+            path_glob TEXT,
+            pattern TEXT NOT NULL,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS format_overrides (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            workspace_id INTEGER NOT NULL,
+            path_glob TEXT NOT NULL,
+            format TEXT NOT NULL,
+            priority INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // Detection order of a workspace's formats; unlisted ones follow
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS format_order (
+            workspace_id INTEGER NOT NULL,
+            format TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (workspace_id, format),
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS sanitizer_scripts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            workspace_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            path_glob TEXT NOT NULL,
+            source TEXT NOT NULL,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS config_sets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            workspace_id INTEGER NOT NULL,
+            pattern TEXT NOT NULL,
+            profile TEXT,
+            write_mode TEXT NOT NULL DEFAULT 'sanitized',
+            UNIQUE (workspace_id, pattern),
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS sanitize_profiles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            reveal_keys TEXT NOT NULL DEFAULT '[]',
+            mask_keys TEXT NOT NULL DEFAULT '[]',
+            line_rules TEXT NOT NULL DEFAULT '[]',
+            placeholder TEXT NOT NULL DEFAULT '***'
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS profile_overrides (
+            config_id INTEGER NOT NULL,
+            profile_id INTEGER NOT NULL,
+            content TEXT NOT NULL,
+            PRIMARY KEY (config_id, profile_id),
+            FOREIGN KEY (config_id) REFERENCES configs(id) ON DELETE CASCADE,
+            FOREIGN KEY (profile_id) REFERENCES sanitize_profiles(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS key_overrides (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            config_id INTEGER NOT NULL,
+            key_path TEXT NOT NULL,
+            value TEXT NOT NULL,
+            UNIQUE (config_id, key_path),
+            FOREIGN KEY (config_id) REFERENCES configs(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS config_revisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            config_id INTEGER NOT NULL,
+            field TEXT NOT NULL,
+            source TEXT NOT NULL,
+            content_hash TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (config_id) REFERENCES configs(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_config_revisions_config ON config_revisions (config_id, field, id)")
+        .execute(&pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS workspace_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            workspace_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (workspace_id, name),
             FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
         )
         "#,
@@ -39,9 +227,409 @@ pub async fn init_db(app_data_dir: &Path) -> Result<SqlitePool, sqlx::Error> {
     .execute(&pool)
     .await?;
 
+    sqlx::query(SNAPSHOT_ENTRIES_TABLE).execute(&pool).await?;
+
+    sqlx::query(FILE_BACKUPS_TABLE).execute(&pool).await?;
+
+    // Hashes of every sanitized output written to disk, so masked content can
+    // be told apart from real edits when it comes back in a merge.
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS sanitized_outputs (
+            config_id INTEGER NOT NULL,
+            hash TEXT NOT NULL,
+            profile TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (config_id, hash),
+            FOREIGN KEY (config_id) REFERENCES configs(id) ON DELETE CASCADE
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at TEXT NOT NULL,
+            actor TEXT NOT NULL,
+            action TEXT NOT NULL,
+            workspace_id INTEGER,
+            config_id INTEGER,
+            target_path TEXT,
+            hash_before TEXT,
+            hash_after TEXT,
+            outcome TEXT NOT NULL,
+            detail TEXT,
+            prev_hash TEXT NOT NULL,
+            entry_hash TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
+    // The audit log is append-only; the hash chain catches edits made outside the app
+    for operation in ["UPDATE", "DELETE"] {
+        sqlx::query(&format!(
+            "CREATE TRIGGER IF NOT EXISTS audit_log_no_{} BEFORE {} ON audit_log
+             BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END",
+            operation.to_lowercase(),
+            operation
+        ))
+        .execute(&pool)
+        .await?;
+    }
+
+    // Columns added after the initial schema
+    ensure_column(&pool, "workspaces", "symlink_policy", "TEXT NOT NULL DEFAULT 'follow'").await?;
+    ensure_column(&pool, "configs", "synced_content", "TEXT").await?;
+    ensure_column(&pool, "configs", "original_hash", "TEXT").await?;
+    ensure_column(&pool, "configs", "sanitized_hash", "TEXT").await?;
+    ensure_column(&pool, "configs", "synced_hash", "TEXT").await?;
+    ensure_column(&pool, "configs", "sanitized_output_hash", "TEXT").await?;
+    ensure_column(&pool, "configs", "encoding", "TEXT NOT NULL DEFAULT 'UTF-8'").await?;
+    ensure_column(&pool, "configs", "has_bom", "INTEGER NOT NULL DEFAULT 0").await?;
+    ensure_column(&pool, "configs", "line_ending", "TEXT NOT NULL DEFAULT 'lf'").await?;
+    ensure_column(&pool, "configs", "kind", "TEXT NOT NULL DEFAULT 'text'").await?;
+    ensure_column(&pool, "snapshot_entries", "kind", "TEXT NOT NULL DEFAULT 'text'").await?;
+    // Left NULL for entries captured before snapshots kept the full row
+    ensure_column(&pool, "snapshot_entries", "synced_hash", "TEXT").await?;
+    ensure_column(&pool, "snapshot_entries", "sanitized_output_hash", "TEXT").await?;
+    ensure_column(&pool, "snapshot_entries", "encoding", "TEXT").await?;
+    ensure_column(&pool, "snapshot_entries", "has_bom", "INTEGER").await?;
+    ensure_column(&pool, "snapshot_entries", "line_ending", "TEXT").await?;
+    ensure_column(&pool, "sanitize_profiles", "mask_keys", "TEXT NOT NULL DEFAULT '[]'").await?;
+    ensure_column(&pool, "sanitize_profiles", "line_rules", "TEXT NOT NULL DEFAULT '[]'").await?;
+    migrate_inline_contents(&pool).await?;
+    migrate_line_endings(&pool).await?;
+    migrate_backup_foreign_key(&pool).await?;
+    // Nothing else uses the pool yet, so no blob can be referenced by a row
+    // that is still being written
+    prune_blobs(&pool).await?;
+
     Ok(pool)
 }
 
+// Entries keep their config id but no foreign key, so a snapshot can bring
+// back configs deleted after it was taken. Metadata columns are NULL for
+// entries captured before snapshots kept the full row.
+const SNAPSHOT_ENTRIES_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS snapshot_entries (
+        snapshot_id INTEGER NOT NULL,
+        config_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        path TEXT NOT NULL,
+        original_hash TEXT NOT NULL,
+        sanitized_hash TEXT,
+        kind TEXT NOT NULL DEFAULT 'text',
+        synced_hash TEXT,
+        sanitized_output_hash TEXT,
+        encoding TEXT,
+        has_bom INTEGER,
+        line_ending TEXT,
+        PRIMARY KEY (snapshot_id, config_id),
+        FOREIGN KEY (snapshot_id) REFERENCES workspace_snapshots(id) ON DELETE CASCADE
+    )
+"#;
+
+// Backups outlive the config and workspace they were taken for: restoring
+// only needs the workspace root and path, and retention is per file
+// (`BACKUPS_PER_FILE`), so there is no foreign key to either.
+const FILE_BACKUPS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS file_backups (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        workspace_id INTEGER NOT NULL,
+        config_id INTEGER,
+        workspace_root TEXT NOT NULL,
+        path TEXT NOT NULL,
+        content_hash TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+"#;
+
+/// Earlier versions deleted backups with their workspace through a foreign
+/// key. Rebuilds the table without it, keeping the ids.
+async fn migrate_backup_foreign_key(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let keys: Vec<String> = sqlx::query_scalar(r#"SELECT "table" FROM pragma_foreign_key_list('file_backups')"#)
+        .fetch_all(&mut *tx)
+        .await?;
+    if !keys.is_empty() {
+        sqlx::query("ALTER TABLE file_backups RENAME TO file_backups_old").execute(&mut *tx).await?;
+        sqlx::query(FILE_BACKUPS_TABLE).execute(&mut *tx).await?;
+        sqlx::query(
+            "INSERT INTO file_backups (id, workspace_id, config_id, workspace_root, path, content_hash, size, created_at)
+             SELECT id, workspace_id, config_id, workspace_root, path, content_hash, size, created_at FROM file_backups_old",
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DROP TABLE file_backups_old").execute(&mut *tx).await?;
+    }
+    tx.commit().await
+}
+
+/// Moves contents stored inline by earlier versions into blobs: config
+/// contents, revision contents and snapshot entries. The inline config and
+/// revision columns are kept, empty, for compatibility with the old schema;
+/// the old snapshot table is rebuilt, as its inline content was required.
+async fn migrate_inline_contents(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let configs: Vec<(i64, String, Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT id, original_content, sanitized_content, synced_content FROM configs WHERE original_hash IS NULL",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for (id, original, sanitized, synced) in configs {
+        let original_hash = put_blob(&mut *tx, &original).await?;
+        let sanitized_hash = match sanitized {
+            Some(content) => Some(put_blob(&mut *tx, &content).await?),
+            None => None,
+        };
+        let synced_hash = match synced {
+            Some(content) => Some(put_blob(&mut *tx, &content).await?),
+            None => None,
+        };
+        sqlx::query(
+            "UPDATE configs SET original_hash = ?, sanitized_hash = ?, synced_hash = ?,
+                 original_content = '', sanitized_content = NULL, synced_content = NULL
+             WHERE id = ?",
+        )
+        .bind(original_hash)
+        .bind(sanitized_hash)
+        .bind(synced_hash)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    if has_column(&mut tx, "config_revisions", "content").await? {
+        let revisions: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, content FROM config_revisions WHERE content IS NOT NULL")
+                .fetch_all(&mut *tx)
+                .await?;
+        for (id, content) in revisions {
+            let hash = put_blob(&mut *tx, &content).await?;
+            sqlx::query("UPDATE config_revisions SET content_hash = ?, content = NULL WHERE id = ?")
+                .bind(hash)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    if has_column(&mut tx, "snapshot_entries", "original_content").await? {
+        let entries: Vec<(i64, i64, String, String, String, Option<String>)> = sqlx::query_as(
+            "SELECT snapshot_id, config_id, name, path, original_content, sanitized_content FROM snapshot_entries",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        sqlx::query("DROP TABLE snapshot_entries").execute(&mut *tx).await?;
+        sqlx::query(SNAPSHOT_ENTRIES_TABLE).execute(&mut *tx).await?;
+        for (snapshot_id, config_id, name, path, original, sanitized) in entries {
+            let original_hash = put_blob(&mut *tx, &original).await?;
+            let sanitized_hash = match sanitized {
+                Some(content) => Some(put_blob(&mut *tx, &content).await?),
+                None => None,
+            };
+            sqlx::query(
+                "INSERT INTO snapshot_entries (snapshot_id, config_id, name, path, original_hash, sanitized_hash)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(snapshot_id)
+            .bind(config_id)
+            .bind(name)
+            .bind(path)
+            .bind(original_hash)
+            .bind(sanitized_hash)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    tx.commit().await
+}
+
+/// `user_version` once stored contents use LF line breaks.
+const LF_CONTENTS_VERSION: i64 = 1;
+
+/// Earlier versions stored text as read, CRLF included, and recorded every
+/// file as LF. Converts CRLF-only contents of configs, revisions and snapshot
+/// entries to LF under new hashes, and takes each config's encoding, BOM and
+/// line endings from its file, or from its content when the file cannot be
+/// read. Contents with mixed line breaks are kept as they are. Runs once.
+async fn migrate_line_endings(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(&mut *tx).await?;
+    if version >= LF_CONTENTS_VERSION {
+        return Ok(());
+    }
+
+    // Old hash to the hash of its LF content
+    let mut renamed: HashMap<String, String> = HashMap::new();
+
+    let configs: Vec<LegacyTextConfig> = sqlx::query_as(
+        "SELECT c.id, c.path, w.root_path, w.symlink_policy, c.original_hash, c.sanitized_hash, c.synced_hash
+         FROM configs c JOIN workspaces w ON w.id = c.workspace_id
+         WHERE c.kind = 'text'",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for config in configs {
+        let Some(original) = get_blob(&mut *tx, &config.original_hash).await? else {
+            continue;
+        };
+        let encoding = match file_system::read_file_bytes(&config.root_path, &config.path, config.symlink_policy) {
+            Ok(bytes) => encoding::decode(&bytes).1,
+            Err(_) => TextEncoding {
+                line_ending: encoding::detect_line_ending(&original),
+                ..TextEncoding::default()
+            },
+        };
+        let original_hash = to_lf(&mut tx, &config.original_hash, &mut renamed).await?;
+        let sanitized_hash = match &config.sanitized_hash {
+            Some(hash) => Some(to_lf(&mut tx, hash, &mut renamed).await?),
+            None => None,
+        };
+        let synced_hash = match &config.synced_hash {
+            Some(hash) => Some(to_lf(&mut tx, hash, &mut renamed).await?),
+            None => None,
+        };
+        sqlx::query(
+            "UPDATE configs SET original_hash = ?, sanitized_hash = ?, synced_hash = ?,
+                 encoding = ?, has_bom = ?, line_ending = ?
+             WHERE id = ?",
+        )
+        .bind(original_hash)
+        .bind(sanitized_hash)
+        .bind(synced_hash)
+        .bind(&encoding.name)
+        .bind(encoding.bom)
+        .bind(encoding.line_ending)
+        .bind(config.id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let revisions: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, content_hash FROM config_revisions WHERE content_hash IS NOT NULL")
+            .fetch_all(&mut *tx)
+            .await?;
+    for (id, hash) in revisions {
+        let content_hash = to_lf(&mut tx, &hash, &mut renamed).await?;
+        sqlx::query("UPDATE config_revisions SET content_hash = ? WHERE id = ?")
+            .bind(content_hash)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let entries: Vec<(i64, i64, String, Option<String>)> = sqlx::query_as(
+        "SELECT snapshot_id, config_id, original_hash, sanitized_hash FROM snapshot_entries WHERE kind = 'text'",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for (snapshot_id, config_id, original_hash, sanitized_hash) in entries {
+        let original_hash = to_lf(&mut tx, &original_hash, &mut renamed).await?;
+        let sanitized_hash = match sanitized_hash {
+            Some(hash) => Some(to_lf(&mut tx, &hash, &mut renamed).await?),
+            None => None,
+        };
+        sqlx::query("UPDATE snapshot_entries SET original_hash = ?, sanitized_hash = ? WHERE snapshot_id = ? AND config_id = ?")
+            .bind(original_hash)
+            .bind(sanitized_hash)
+            .bind(snapshot_id)
+            .bind(config_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // Sanitized outputs were hashed from the CRLF text written out, which was
+    // the sanitized content; their files now decode to its LF form
+    for (old, new) in renamed.iter().filter(|(old, new)| old != new) {
+        sqlx::query("UPDATE configs SET sanitized_output_hash = ? WHERE sanitized_output_hash = ?")
+            .bind(new)
+            .bind(old)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT OR IGNORE INTO sanitized_outputs (config_id, hash, profile, created_at)
+             SELECT config_id, ?, profile, created_at FROM sanitized_outputs WHERE hash = ?",
+        )
+        .bind(new)
+        .bind(old)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query(&format!("PRAGMA user_version = {}", LF_CONTENTS_VERSION))
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+#[derive(FromRow)]
+struct LegacyTextConfig {
+    id: i64,
+    path: String,
+    root_path: String,
+    symlink_policy: SymlinkPolicy,
+    original_hash: String,
+    sanitized_hash: Option<String>,
+    synced_hash: Option<String>,
+}
+
+/// Hash of a stored content as it decodes now, without a leading BOM and with
+/// CRLF-only line breaks as LF, storing that form if it differs. Contents with
+/// mixed line breaks keep them; missing contents keep their hash.
+async fn to_lf(
+    conn: &mut SqliteConnection,
+    hash: &str,
+    renamed: &mut HashMap<String, String>,
+) -> Result<String, sqlx::Error> {
+    if let Some(new_hash) = renamed.get(hash) {
+        return Ok(new_hash.clone());
+    }
+    let new_hash = match get_blob(&mut *conn, hash).await? {
+        Some(content) => {
+            let text = content.strip_prefix('\u{FEFF}').unwrap_or(&content);
+            let text = match encoding::detect_line_ending(text) {
+                LineEnding::Crlf => text.replace("\r\n", "\n"),
+                _ => text.to_string(),
+            };
+            if text == content {
+                hash.to_string()
+            } else {
+                put_blob(&mut *conn, &text).await?
+            }
+        }
+        None => hash.to_string(),
+    };
+    renamed.insert(hash.to_string(), new_hash.clone());
+    Ok(new_hash)
+}
+
+async fn has_column(conn: &mut SqliteConnection, table: &str, column: &str) -> Result<bool, sqlx::Error> {
+    let columns: Vec<String> = sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info('{}')", table))
+        .fetch_all(conn)
+        .await?;
+    Ok(columns.iter().any(|c| c == column))
+}
+
+/// Adds a column to an existing table if it is missing.
+async fn ensure_column(pool: &SqlitePool, table: &str, column: &str, definition: &str) -> Result<(), sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    if !has_column(&mut conn, table, column).await? {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
 // --- Workspace CRUD ---
 
 pub async fn add_workspace(pool: &SqlitePool, name: &str, root_path: &str) -> Result<i64, sqlx::Error> {
@@ -57,80 +645,1509 @@ pub async fn add_workspace(pool: &SqlitePool, name: &str, root_path: &str) -> Re
 }
 
 pub async fn get_all_workspaces(pool: &SqlitePool) -> Result<Vec<Workspace>, sqlx::Error> {
-    sqlx::query_as::<_, Workspace>("SELECT id, name, root_path FROM workspaces ORDER BY id")
+    sqlx::query_as::<_, Workspace>("SELECT id, name, root_path, symlink_policy FROM workspaces ORDER BY id")
         .fetch_all(pool)
         .await
 }
 
+pub async fn get_workspace_by_root(pool: &SqlitePool, root_path: &str) -> Result<Option<Workspace>, sqlx::Error> {
+    sqlx::query_as::<_, Workspace>("SELECT id, name, root_path, symlink_policy FROM workspaces WHERE root_path = ?")
+        .bind(root_path)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_workspace_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Workspace>, sqlx::Error> {
+    sqlx::query_as::<_, Workspace>("SELECT id, name, root_path, symlink_policy FROM workspaces WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn set_symlink_policy(pool: &SqlitePool, id: i64, policy: SymlinkPolicy) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE workspaces SET symlink_policy = ? WHERE id = ?")
+        .bind(policy)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Removes a workspace with its configs, rules and snapshots in one
+/// transaction, so a failure leaves nothing half-deleted. Backups of its
+/// files are kept.
 pub async fn delete_workspace(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
-    sqlx::query("DELETE FROM configs WHERE workspace_id = ?").bind(id).execute(pool).await?;
-    sqlx::query("DELETE FROM workspaces WHERE id = ?").bind(id).execute(pool).await?;
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM key_overrides WHERE config_id IN (SELECT id FROM configs WHERE workspace_id = ?)")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM profile_overrides WHERE config_id IN (SELECT id FROM configs WHERE workspace_id = ?)")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM config_revisions WHERE config_id IN (SELECT id FROM configs WHERE workspace_id = ?)")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM sanitized_outputs WHERE config_id IN (SELECT id FROM configs WHERE workspace_id = ?)")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM snapshot_entries WHERE snapshot_id IN (SELECT id FROM workspace_snapshots WHERE workspace_id = ?)")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM workspace_snapshots WHERE workspace_id = ?").bind(id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM configs WHERE workspace_id = ?").bind(id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM line_rules WHERE workspace_id = ?").bind(id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM format_overrides WHERE workspace_id = ?").bind(id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM format_order WHERE workspace_id = ?").bind(id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM sanitizer_scripts WHERE workspace_id = ?").bind(id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM config_sets WHERE workspace_id = ?").bind(id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM workspaces WHERE id = ?").bind(id).execute(&mut *tx).await?;
+    tx.commit().await
+}
+
+// --- Blob storage ---
+
+/// Stores `content` compressed under its SHA-256 hash, reusing the existing
+/// blob when the same content is already stored. Returns the hash.
+pub async fn put_blob<'e, E>(executor: E, content: &str) -> Result<String, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    put_blob_bytes(executor, content.as_bytes()).await
+}
+
+pub async fn put_blob_bytes<'e, E>(executor: E, content: &[u8]) -> Result<String, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let hash = file_system::calculate_bytes_hash(content);
+    let data = blobs::compress(content).map_err(sqlx::Error::Io)?;
+
+    sqlx::query("INSERT OR IGNORE INTO blobs (hash, size, data) VALUES (?, ?, ?)")
+        .bind(&hash)
+        .bind(content.len() as i64)
+        .bind(data)
+        .execute(executor)
+        .await?;
+    Ok(hash)
+}
+
+pub async fn get_blob<'e, E>(executor: E, hash: &str) -> Result<Option<String>, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let data: Option<Vec<u8>> = sqlx::query_scalar("SELECT data FROM blobs WHERE hash = ?")
+        .bind(hash)
+        .fetch_optional(executor)
+        .await?;
+    data.as_deref().map(decode_blob).transpose()
+}
+
+pub async fn get_blob_bytes(pool: &SqlitePool, hash: &str) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let data: Option<Vec<u8>> = sqlx::query_scalar("SELECT data FROM blobs WHERE hash = ?")
+        .bind(hash)
+        .fetch_optional(pool)
+        .await?;
+    data.as_deref()
+        .map(|data| blobs::decompress(data).map_err(|e| sqlx::Error::Decode(Box::new(e))))
+        .transpose()
+}
+
+/// A row references a blob that is not stored.
+fn missing_blob(table: &str, id: i64) -> sqlx::Error {
+    sqlx::Error::Decode(format!("content of {} {} is missing from blob storage", table, id).into())
+}
+
+fn decode_blob(data: &[u8]) -> Result<String, sqlx::Error> {
+    blobs::decompress_text(data).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Deletes blobs no longer referenced by any config, revision, snapshot or
+/// backup. Only run at startup: blobs are written before the rows that
+/// reference them, so a prune running alongside writes could remove a blob
+/// whose row is about to be committed.
+async fn prune_blobs(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM blobs WHERE hash NOT IN (
+             SELECT original_hash FROM configs WHERE original_hash IS NOT NULL
+             UNION SELECT sanitized_hash FROM configs WHERE sanitized_hash IS NOT NULL
+             UNION SELECT synced_hash FROM configs WHERE synced_hash IS NOT NULL
+             UNION SELECT content_hash FROM config_revisions WHERE content_hash IS NOT NULL
+             UNION SELECT original_hash FROM snapshot_entries WHERE original_hash IS NOT NULL
+             UNION SELECT sanitized_hash FROM snapshot_entries WHERE sanitized_hash IS NOT NULL
+             UNION SELECT content_hash FROM file_backups
+         )",
+    )
+    .execute(pool)
+    .await?;
     Ok(())
 }
 
 // --- Config CRUD ---
 
+// Blobs are left-joined so a config whose blob is missing is reported as an
+// error instead of silently dropping out of the result.
+const CONFIG_SELECT: &str = "SELECT c.id, c.workspace_id, c.name, c.path, c.kind,
+        o.data AS original_data, c.sanitized_hash, s.data AS sanitized_data, c.encoding, c.has_bom, c.line_ending
+    FROM configs c
+    LEFT JOIN blobs o ON o.hash = c.original_hash
+    LEFT JOIN blobs s ON s.hash = c.sanitized_hash";
+
+#[derive(FromRow)]
+struct ConfigRow {
+    id: i64,
+    workspace_id: i64,
+    name: String,
+    path: String,
+    kind: ConfigKind,
+    original_data: Option<Vec<u8>>,
+    sanitized_hash: Option<String>,
+    sanitized_data: Option<Vec<u8>>,
+    encoding: String,
+    has_bom: bool,
+    line_ending: LineEnding,
+}
+
+impl ConfigRow {
+    fn into_config(self) -> Result<Config, sqlx::Error> {
+        let original_data = self.original_data.ok_or_else(|| missing_blob("config", self.id))?;
+        if self.sanitized_hash.is_some() && self.sanitized_data.is_none() {
+            return Err(missing_blob("config", self.id));
+        }
+        let (original_content, sanitized_content) = match self.kind {
+            ConfigKind::Text => (
+                decode_blob(&original_data)?,
+                self.sanitized_data.as_deref().map(decode_blob).transpose()?,
+            ),
+            ConfigKind::Binary => (String::new(), None),
+        };
+        Ok(Config {
+            id: self.id,
+            workspace_id: self.workspace_id,
+            name: self.name,
+            path: self.path,
+            kind: self.kind,
+            original_content,
+            sanitized_content,
+            encoding: TextEncoding {
+                name: self.encoding,
+                bom: self.has_bom,
+                line_ending: self.line_ending,
+            },
+        })
+    }
+}
+
 pub async fn add_config(pool: &SqlitePool, config: CreateConfig) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let id = insert_config(&mut tx, &config).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Adds an opaque config. Its bytes are stored as is and both the original
+/// and the synced hash point at them.
+pub async fn add_binary_config(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    name: &str,
+    path: &str,
+    content: &[u8],
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let id = insert_binary_config(&mut tx, workspace_id, name, path, content).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Adds several configs in one transaction, so either all of them are
+/// imported or none. Text configs get their imported content as first
+/// revision. Returns the ids in the order of `files`.
+pub async fn import_configs(pool: &SqlitePool, files: &[ImportedFile]) -> Result<Vec<i64>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let mut ids = Vec::with_capacity(files.len());
+    for file in files {
+        let id = match file {
+            ImportedFile::Text(config) => {
+                let id = insert_config(&mut tx, config).await?;
+                sqlx::query("INSERT INTO config_revisions (config_id, field, source, content_hash) VALUES (?, ?, ?, ?)")
+                    .bind(id)
+                    .bind(RevisionField::Original)
+                    .bind(RevisionSource::Import)
+                    .bind(file_system::calculate_content_hash(&config.original_content))
+                    .execute(&mut *tx)
+                    .await?;
+                id
+            }
+            ImportedFile::Binary { workspace_id, name, path, content } => {
+                insert_binary_config(&mut tx, *workspace_id, name, path, content).await?
+            }
+        };
+        ids.push(id);
+    }
+
+    tx.commit().await?;
+    Ok(ids)
+}
+
+async fn insert_config(conn: &mut SqliteConnection, config: &CreateConfig) -> Result<i64, sqlx::Error> {
+    let hash = put_blob(&mut *conn, &config.original_content).await?;
     let result = sqlx::query(
-        "INSERT INTO configs (workspace_id, name, path, original_content, sanitized_content) VALUES (?, ?, ?, ?, NULL)",
+        "INSERT INTO configs (workspace_id, name, path, original_content, original_hash, synced_hash, encoding, has_bom, line_ending)
+         VALUES (?, ?, ?, '', ?, ?, ?, ?, ?)",
     )
     .bind(config.workspace_id)
     .bind(&config.name)
     .bind(&config.path)
-    .bind(&config.original_content)
-    .execute(pool)
+    .bind(&hash)
+    .bind(&hash)
+    .bind(&config.encoding.name)
+    .bind(config.encoding.bom)
+    .bind(config.encoding.line_ending)
+    .execute(&mut *conn)
     .await?;
 
     Ok(result.last_insert_rowid())
 }
 
-pub async fn get_configs_by_workspace(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<Config>, sqlx::Error> {
-    sqlx::query_as::<_, Config>(
-        "SELECT id, workspace_id, name, path, original_content, sanitized_content FROM configs WHERE workspace_id = ? ORDER BY path",
+async fn insert_binary_config(
+    conn: &mut SqliteConnection,
+    workspace_id: i64,
+    name: &str,
+    path: &str,
+    content: &[u8],
+) -> Result<i64, sqlx::Error> {
+    let hash = put_blob_bytes(&mut *conn, content).await?;
+    let result = sqlx::query(
+        "INSERT INTO configs (workspace_id, name, path, original_content, original_hash, synced_hash, kind)
+         VALUES (?, ?, ?, '', ?, ?, ?)",
     )
     .bind(workspace_id)
-    .fetch_all(pool)
-    .await
-}
+    .bind(name)
+    .bind(path)
+    .bind(&hash)
+    .bind(&hash)
+    .bind(ConfigKind::Binary)
+    .execute(&mut *conn)
+    .await?;
 
-pub async fn get_all_configs(pool: &SqlitePool) -> Result<Vec<Config>, sqlx::Error> {
-    sqlx::query_as::<_, Config>(
-        "SELECT id, workspace_id, name, path, original_content, sanitized_content FROM configs ORDER BY workspace_id, path",
-    )
-    .fetch_all(pool)
-    .await
+    Ok(result.last_insert_rowid())
 }
-pub async fn get_config_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Config>, sqlx::Error> {
-    sqlx::query_as::<_, Config>(
-        "SELECT id, workspace_id, name, path, original_content, sanitized_content FROM configs WHERE id = ?",
-    )
+
+/// Bytes of a binary config: its original content, or for `Sanitized` the
+/// replacement file written by sanitized writes, if one was supplied.
+pub async fn get_binary_content(
+    pool: &SqlitePool,
+    id: i64,
+    field: RevisionField,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    let column = match field {
+        RevisionField::Original => "original_hash",
+        RevisionField::Sanitized => "sanitized_hash",
+    };
+    let hash: Option<String> = sqlx::query_scalar(&format!("SELECT {} FROM configs WHERE id = ?", column))
     .bind(id)
     .fetch_optional(pool)
-    .await
+    .await?
+    .flatten();
+
+    match hash {
+        Some(hash) => get_blob_bytes(pool, &hash).await,
+        None => Ok(None),
+    }
+}
+
+/// Replaces the bytes of a binary config. Only the `Sanitized` replacement
+/// can be cleared with `None`.
+pub async fn set_binary_content(
+    pool: &SqlitePool,
+    id: i64,
+    field: RevisionField,
+    content: Option<&[u8]>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let hash = match content {
+        Some(content) => Some(put_blob_bytes(&mut *tx, content).await?),
+        None => None,
+    };
+    let column = match field {
+        RevisionField::Original => "original_hash",
+        RevisionField::Sanitized => "sanitized_hash",
+    };
+    sqlx::query(&format!("UPDATE configs SET {} = ? WHERE id = ?", column))
+        .bind(hash)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+pub async fn get_configs_by_workspace(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<Config>, sqlx::Error> {
+    sqlx::query_as::<_, ConfigRow>(&format!("{} WHERE c.workspace_id = ? ORDER BY c.path", CONFIG_SELECT))
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(ConfigRow::into_config)
+        .collect()
+}
+
+const CONFIG_SUMMARY_SELECT: &str =
+    "SELECT id, workspace_id, name, path, kind, encoding, has_bom, line_ending FROM configs";
+
+#[derive(FromRow)]
+struct ConfigSummaryRow {
+    id: i64,
+    workspace_id: i64,
+    name: String,
+    path: String,
+    kind: ConfigKind,
+    encoding: String,
+    has_bom: bool,
+    line_ending: LineEnding,
+}
+
+impl From<ConfigSummaryRow> for ConfigSummary {
+    fn from(row: ConfigSummaryRow) -> Self {
+        ConfigSummary {
+            id: row.id,
+            workspace_id: row.workspace_id,
+            name: row.name,
+            path: row.path,
+            kind: row.kind,
+            encoding: TextEncoding {
+                name: row.encoding,
+                bom: row.has_bom,
+                line_ending: row.line_ending,
+            },
+        }
+    }
+}
+
+/// Every config without its contents, so listing does not read any blob.
+pub async fn get_all_configs(pool: &SqlitePool) -> Result<Vec<ConfigSummary>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ConfigSummaryRow>(&format!("{} ORDER BY workspace_id, path", CONFIG_SUMMARY_SELECT))
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(ConfigSummary::from).collect())
+}
+
+pub async fn get_config_summaries(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<ConfigSummary>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ConfigSummaryRow>(&format!("{} WHERE workspace_id = ? ORDER BY path", CONFIG_SUMMARY_SELECT))
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(ConfigSummary::from).collect())
+}
+
+pub async fn get_config_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Config>, sqlx::Error> {
+    sqlx::query_as::<_, ConfigRow>(&format!("{} WHERE c.id = ?", CONFIG_SELECT))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .map(ConfigRow::into_config)
+        .transpose()
 }
 
-pub async fn update_original_content(pool: &SqlitePool, id: i64, content: &str) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE configs SET original_content = ? WHERE id = ?")
-        .bind(content)
+const CONFIG_HASHES_SELECT: &str =
+    "SELECT id, workspace_id, path, kind, original_hash, synced_hash, sanitized_output_hash FROM configs";
+
+pub async fn get_config_hashes(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<ConfigHashes>, sqlx::Error> {
+    sqlx::query_as(&format!("{} WHERE workspace_id = ?", CONFIG_HASHES_SELECT))
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_config_hashes_by_id(pool: &SqlitePool, id: i64) -> Result<Option<ConfigHashes>, sqlx::Error> {
+    sqlx::query_as(&format!("{} WHERE id = ?", CONFIG_HASHES_SELECT))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn update_config_path(pool: &SqlitePool, id: i64, name: &str, path: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE configs SET name = ?, path = ? WHERE id = ?")
+        .bind(name)
+        .bind(path)
         .bind(id)
         .execute(pool)
         .await?;
     Ok(())
 }
 
-pub async fn update_sanitized_content(pool: &SqlitePool, id: i64, content: Option<&str>) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE configs SET sanitized_content = ? WHERE id = ?")
-        .bind(content)
+/// The content last known to match the file on disk, used as the merge base.
+pub async fn get_synced_content(pool: &SqlitePool, id: i64) -> Result<Option<String>, sqlx::Error> {
+    let row: Option<(Option<String>, Option<Vec<u8>>)> =
+        sqlx::query_as("SELECT c.synced_hash, b.data FROM configs c LEFT JOIN blobs b ON b.hash = c.synced_hash WHERE c.id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+    match row {
+        Some((Some(_), None)) => Err(missing_blob("config", id)),
+        Some((_, data)) => data.as_deref().map(decode_blob).transpose(),
+        None => Ok(None),
+    }
+}
+
+pub async fn update_synced_content(pool: &SqlitePool, id: i64, content: &str) -> Result<(), sqlx::Error> {
+    update_synced_bytes(pool, id, content.as_bytes()).await
+}
+
+pub async fn update_synced_bytes(pool: &SqlitePool, id: i64, content: &[u8]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let hash = put_blob_bytes(&mut *tx, content).await?;
+    sqlx::query("UPDATE configs SET synced_hash = ? WHERE id = ?")
+        .bind(hash)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Marks content that is already stored as a blob, such as the original just
+/// written to disk, as the last-synced version.
+pub async fn update_synced_hash(pool: &SqlitePool, id: i64, hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE configs SET synced_hash = ? WHERE id = ?")
+        .bind(hash)
         .bind(id)
         .execute(pool)
         .await?;
     Ok(())
 }
 
+/// Records the hash of sanitized output written to disk, so the file can be
+/// recognised as ours rather than as an external change.
+pub async fn record_sanitized_output(
+    pool: &SqlitePool,
+    id: i64,
+    hash: &str,
+    profile: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE configs SET sanitized_output_hash = ? WHERE id = ?")
+        .bind(hash)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO sanitized_outputs (config_id, hash, profile) VALUES (?, ?, ?)
+         ON CONFLICT (config_id, hash) DO UPDATE SET profile = excluded.profile, created_at = CURRENT_TIMESTAMP",
+    )
+    .bind(id)
+    .bind(hash)
+    .bind(profile)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+pub async fn is_sanitized_output(pool: &SqlitePool, id: i64, hash: &str) -> Result<bool, sqlx::Error> {
+    let found: Option<i64> = sqlx::query_scalar("SELECT 1 FROM sanitized_outputs WHERE config_id = ? AND hash = ?")
+        .bind(id)
+        .bind(hash)
+        .fetch_optional(pool)
+        .await?;
+    Ok(found.is_some())
+}
+
+/// Whether a sanitized version of the config has ever been written.
+pub async fn has_sanitized_outputs(pool: &SqlitePool, id: i64) -> Result<bool, sqlx::Error> {
+    let found: Option<i64> = sqlx::query_scalar("SELECT 1 FROM sanitized_outputs WHERE config_id = ? LIMIT 1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(found.is_some())
+}
+
+/// Removes a config with its overrides, revisions and recorded outputs in one
+/// transaction.
 pub async fn delete_config(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM key_overrides WHERE config_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM profile_overrides WHERE config_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM config_revisions WHERE config_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM sanitized_outputs WHERE config_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
     sqlx::query("DELETE FROM configs WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+// --- Line rule CRUD ---
+
+pub async fn add_line_rule(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    path_glob: Option<&str>,
+    pattern: &str,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO line_rules (workspace_id, path_glob, pattern) VALUES (?, ?, ?)",
+    )
+    .bind(workspace_id)
+    .bind(path_glob)
+    .bind(pattern)
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn get_line_rules_by_workspace(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<LineRule>, sqlx::Error> {
+    sqlx::query_as::<_, LineRule>(
+        "SELECT id, workspace_id, path_glob, pattern FROM line_rules WHERE workspace_id = ? ORDER BY id",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn delete_line_rule(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM line_rules WHERE id = ?")
         .bind(id)
         .execute(pool)
         .await?;
     Ok(())
 }
+
+// --- Format override CRUD ---
+
+pub async fn add_format_override(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    path_glob: &str,
+    format: &str,
+    priority: i64,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO format_overrides (workspace_id, path_glob, format, priority) VALUES (?, ?, ?, ?)",
+    )
+    .bind(workspace_id)
+    .bind(path_glob)
+    .bind(format)
+    .bind(priority)
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn get_format_overrides_by_workspace(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<FormatOverride>, sqlx::Error> {
+    sqlx::query_as::<_, FormatOverride>(
+        "SELECT id, workspace_id, path_glob, format, priority FROM format_overrides WHERE workspace_id = ? ORDER BY priority, id",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn delete_format_override(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM format_overrides WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_format_order(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT format FROM format_order WHERE workspace_id = ? ORDER BY position")
+        .bind(workspace_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// Replaces a workspace's format order.
+pub async fn set_format_order(pool: &SqlitePool, workspace_id: i64, formats: &[String]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM format_order WHERE workspace_id = ?")
+        .bind(workspace_id)
+        .execute(&mut *tx)
+        .await?;
+    for (position, format) in formats.iter().enumerate() {
+        sqlx::query("INSERT INTO format_order (workspace_id, format, position) VALUES (?, ?, ?)")
+            .bind(workspace_id)
+            .bind(format)
+            .bind(position as i64)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
+// --- Sanitizer script CRUD ---
+
+pub async fn add_sanitizer_script(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    name: &str,
+    path_glob: &str,
+    source: &str,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO sanitizer_scripts (workspace_id, name, path_glob, source) VALUES (?, ?, ?, ?)",
+    )
+    .bind(workspace_id)
+    .bind(name)
+    .bind(path_glob)
+    .bind(source)
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn get_sanitizer_scripts_by_workspace(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<SanitizerScript>, sqlx::Error> {
+    sqlx::query_as::<_, SanitizerScript>(
+        "SELECT id, workspace_id, name, path_glob, source FROM sanitizer_scripts WHERE workspace_id = ? ORDER BY id",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn delete_sanitizer_script(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM sanitizer_scripts WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// --- Config set CRUD ---
+
+pub async fn add_config_set(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    pattern: &str,
+    profile: Option<&str>,
+    write_mode: WriteMode,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO config_sets (workspace_id, pattern, profile, write_mode) VALUES (?, ?, ?, ?)",
+    )
+    .bind(workspace_id)
+    .bind(pattern)
+    .bind(profile)
+    .bind(write_mode)
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn get_config_sets_by_workspace(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<ConfigSet>, sqlx::Error> {
+    sqlx::query_as::<_, ConfigSet>(
+        "SELECT id, workspace_id, pattern, profile, write_mode FROM config_sets WHERE workspace_id = ? ORDER BY id",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_config_set(pool: &SqlitePool, id: i64) -> Result<Option<ConfigSet>, sqlx::Error> {
+    sqlx::query_as::<_, ConfigSet>("SELECT id, workspace_id, pattern, profile, write_mode FROM config_sets WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn delete_config_set(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM config_sets WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// --- Sanitize profile CRUD ---
+
+pub async fn add_profile(
+    pool: &SqlitePool,
+    name: &str,
+    reveal_keys: &[String],
+    mask_keys: &[String],
+    line_rules: &[String],
+    placeholder: &str,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO sanitize_profiles (name, reveal_keys, mask_keys, line_rules, placeholder) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(name)
+    .bind(sqlx::types::Json(reveal_keys))
+    .bind(sqlx::types::Json(mask_keys))
+    .bind(sqlx::types::Json(line_rules))
+    .bind(placeholder)
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn get_all_profiles(pool: &SqlitePool) -> Result<Vec<SanitizeProfile>, sqlx::Error> {
+    sqlx::query_as::<_, SanitizeProfile>(
+        "SELECT id, name, reveal_keys, mask_keys, line_rules, placeholder FROM sanitize_profiles ORDER BY name",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_profile_by_name(pool: &SqlitePool, name: &str) -> Result<Option<SanitizeProfile>, sqlx::Error> {
+    sqlx::query_as::<_, SanitizeProfile>(
+        "SELECT id, name, reveal_keys, mask_keys, line_rules, placeholder FROM sanitize_profiles WHERE name = ?",
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn update_profile(
+    pool: &SqlitePool,
+    id: i64,
+    name: &str,
+    reveal_keys: &[String],
+    mask_keys: &[String],
+    line_rules: &[String],
+    placeholder: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE sanitize_profiles SET name = ?, reveal_keys = ?, mask_keys = ?, line_rules = ?, placeholder = ? WHERE id = ?",
+    )
+    .bind(name)
+    .bind(sqlx::types::Json(reveal_keys))
+    .bind(sqlx::types::Json(mask_keys))
+    .bind(sqlx::types::Json(line_rules))
+    .bind(placeholder)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_profile(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM profile_overrides WHERE profile_id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM sanitize_profiles WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_profile_override(
+    pool: &SqlitePool,
+    config_id: i64,
+    profile_id: i64,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT content FROM profile_overrides WHERE config_id = ? AND profile_id = ?")
+        .bind(config_id)
+        .bind(profile_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn set_profile_override(
+    pool: &SqlitePool,
+    config_id: i64,
+    profile_id: i64,
+    content: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    match content {
+        Some(content) => {
+            sqlx::query(
+                "INSERT INTO profile_overrides (config_id, profile_id, content) VALUES (?, ?, ?)
+                 ON CONFLICT(config_id, profile_id) DO UPDATE SET content = excluded.content",
+            )
+            .bind(config_id)
+            .bind(profile_id)
+            .bind(content)
+            .execute(pool)
+            .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM profile_overrides WHERE config_id = ? AND profile_id = ?")
+                .bind(config_id)
+                .bind(profile_id)
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+// --- Key override CRUD ---
+
+pub async fn get_key_overrides(pool: &SqlitePool, config_id: i64) -> Result<Vec<KeyOverride>, sqlx::Error> {
+    sqlx::query_as::<_, KeyOverride>(
+        "SELECT id, config_id, key_path, value FROM key_overrides WHERE config_id = ? ORDER BY key_path",
+    )
+    .bind(config_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn set_key_override(
+    pool: &SqlitePool,
+    config_id: i64,
+    key_path: &str,
+    value: &str,
+) -> Result<i64, sqlx::Error> {
+    let id = sqlx::query_scalar(
+        "INSERT INTO key_overrides (config_id, key_path, value) VALUES (?, ?, ?)
+         ON CONFLICT(config_id, key_path) DO UPDATE SET value = excluded.value
+         RETURNING id",
+    )
+    .bind(config_id)
+    .bind(key_path)
+    .bind(value)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id)
+}
+
+pub async fn delete_key_override(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM key_overrides WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// --- Revision history ---
+
+// An editor save is folded into the latest editor revision if that revision
+// was saved within the gap and started within the span, so a long editing
+// session still produces a revision every few minutes.
+const REVISION_COALESCE_GAP: &str = "-60 seconds";
+const REVISION_COALESCE_SPAN: &str = "-600 seconds";
+
+/// Records a new version of a config's content. Editor saves are coalesced
+/// into the latest revision while the user keeps typing, and saving the same
+/// content as the latest revision records nothing. Returns the revision id.
+pub async fn record_revision(
+    pool: &SqlitePool,
+    config_id: i64,
+    field: RevisionField,
+    source: RevisionSource,
+    content: Option<&str>,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let id = insert_revision(&mut tx, config_id, field, source, content).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Replaces a config's original or sanitized content and records the new
+/// revision in one transaction. When the field has no history yet,
+/// `previous` is recorded first so the change can be rolled back.
+pub async fn save_content(
+    pool: &SqlitePool,
+    config_id: i64,
+    field: RevisionField,
+    previous: Option<&str>,
+    content: Option<&str>,
+    source: RevisionSource,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // Writing first takes the write lock up front; a deferred transaction
+    // that reads first fails instead of waiting when it has to upgrade
+    let query = match field {
+        RevisionField::Original => "UPDATE configs SET original_hash = ? WHERE id = ?",
+        RevisionField::Sanitized => "UPDATE configs SET sanitized_hash = ? WHERE id = ?",
+    };
+    let stored = match field {
+        RevisionField::Original => Some(content.unwrap_or_default()),
+        RevisionField::Sanitized => content,
+    };
+    let hash = match stored {
+        Some(stored) => Some(put_blob(&mut *tx, stored).await?),
+        None => None,
+    };
+    sqlx::query(query).bind(hash).bind(config_id).execute(&mut *tx).await?;
+
+    let has_history: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM config_revisions WHERE config_id = ? AND field = ?)")
+            .bind(config_id)
+            .bind(field)
+            .fetch_one(&mut *tx)
+            .await?;
+    if let (Some(previous), false) = (previous, has_history) {
+        insert_revision(&mut tx, config_id, field, RevisionSource::Import, Some(previous)).await?;
+    }
+
+    insert_revision(&mut tx, config_id, field, source, content).await?;
+    tx.commit().await
+}
+
+async fn insert_revision(
+    conn: &mut SqliteConnection,
+    config_id: i64,
+    field: RevisionField,
+    source: RevisionSource,
+    content: Option<&str>,
+) -> Result<i64, sqlx::Error> {
+    let content_hash = match content {
+        Some(content) => Some(put_blob(&mut *conn, content).await?),
+        None => None,
+    };
+
+    let latest: Option<(i64, RevisionSource, Option<String>, bool)> = sqlx::query_as(
+        "SELECT id, source, content_hash,
+                updated_at >= datetime('now', ?) AND created_at >= datetime('now', ?)
+         FROM config_revisions WHERE config_id = ? AND field = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(REVISION_COALESCE_GAP)
+    .bind(REVISION_COALESCE_SPAN)
+    .bind(config_id)
+    .bind(field)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some((id, latest_source, latest_hash, recent)) = latest {
+        if latest_hash == content_hash {
+            return Ok(id);
+        }
+        if source == RevisionSource::Editor && latest_source == RevisionSource::Editor && recent {
+            sqlx::query("UPDATE config_revisions SET content_hash = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(content_hash)
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            return Ok(id);
+        }
+    }
+
+    let result = sqlx::query("INSERT INTO config_revisions (config_id, field, source, content_hash) VALUES (?, ?, ?, ?)")
+        .bind(config_id)
+        .bind(field)
+        .bind(source)
+        .bind(content_hash)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn get_revisions(pool: &SqlitePool, config_id: i64) -> Result<Vec<RevisionSummary>, sqlx::Error> {
+    sqlx::query_as::<_, RevisionSummary>(
+        "SELECT id, config_id, field, source, content_hash, created_at, updated_at
+         FROM config_revisions WHERE config_id = ? ORDER BY id DESC",
+    )
+    .bind(config_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_revision(pool: &SqlitePool, id: i64) -> Result<Option<ConfigRevision>, sqlx::Error> {
+    let summary = sqlx::query_as::<_, RevisionSummary>(
+        "SELECT id, config_id, field, source, content_hash, created_at, updated_at
+         FROM config_revisions WHERE id = ?",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(summary) = summary else {
+        return Ok(None);
+    };
+    let content = match &summary.content_hash {
+        Some(hash) => Some(get_blob(pool, hash).await?.ok_or_else(|| missing_blob("revision", id))?),
+        None => None,
+    };
+    Ok(Some(ConfigRevision {
+        id: summary.id,
+        config_id: summary.config_id,
+        field: summary.field,
+        source: summary.source,
+        content,
+        content_hash: summary.content_hash,
+        created_at: summary.created_at,
+        updated_at: summary.updated_at,
+    }))
+}
+
+// --- Workspace snapshots ---
+
+/// Captures every config of a workspace under `name`. Contents are shared
+/// with the configs through the blob table, so a snapshot only stores hashes.
+/// Returns the snapshot id.
+pub async fn create_snapshot(pool: &SqlitePool, workspace_id: i64, name: &str) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let id = sqlx::query("INSERT INTO workspace_snapshots (workspace_id, name) VALUES (?, ?)")
+        .bind(workspace_id)
+        .bind(name)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+    sqlx::query(
+        "INSERT INTO snapshot_entries (snapshot_id, config_id, name, path, original_hash, sanitized_hash, kind,
+                                       synced_hash, sanitized_output_hash, encoding, has_bom, line_ending)
+         SELECT ?, id, name, path, original_hash, sanitized_hash, kind,
+                synced_hash, sanitized_output_hash, encoding, has_bom, line_ending
+         FROM configs WHERE workspace_id = ?",
+    )
+    .bind(id)
+    .bind(workspace_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+pub async fn get_snapshots(pool: &SqlitePool, workspace_id: i64) -> Result<Vec<WorkspaceSnapshot>, sqlx::Error> {
+    sqlx::query_as::<_, WorkspaceSnapshot>(
+        "SELECT s.id, s.workspace_id, s.name, s.created_at,
+                (SELECT COUNT(*) FROM snapshot_entries e WHERE e.snapshot_id = s.id) AS config_count
+         FROM workspace_snapshots s WHERE s.workspace_id = ? ORDER BY s.id DESC",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_snapshot(pool: &SqlitePool, id: i64) -> Result<Option<WorkspaceSnapshot>, sqlx::Error> {
+    sqlx::query_as::<_, WorkspaceSnapshot>(
+        "SELECT s.id, s.workspace_id, s.name, s.created_at,
+                (SELECT COUNT(*) FROM snapshot_entries e WHERE e.snapshot_id = s.id) AS config_count
+         FROM workspace_snapshots s WHERE s.id = ?",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+#[derive(FromRow)]
+struct SnapshotEntryRow {
+    snapshot_id: i64,
+    config_id: i64,
+    name: String,
+    path: String,
+    kind: ConfigKind,
+    original_data: Option<Vec<u8>>,
+    sanitized_hash: Option<String>,
+    sanitized_data: Option<Vec<u8>>,
+}
+
+pub async fn get_snapshot_entries(pool: &SqlitePool, snapshot_id: i64) -> Result<Vec<SnapshotEntry>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, SnapshotEntryRow>(
+        "SELECT e.snapshot_id, e.config_id, e.name, e.path, e.kind, o.data AS original_data, e.sanitized_hash,
+                s.data AS sanitized_data
+         FROM snapshot_entries e
+         LEFT JOIN blobs o ON o.hash = e.original_hash
+         LEFT JOIN blobs s ON s.hash = e.sanitized_hash
+         WHERE e.snapshot_id = ? ORDER BY e.path",
+    )
+    .bind(snapshot_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let original_data = row.original_data.ok_or_else(|| missing_blob("snapshot entry", row.config_id))?;
+            if row.sanitized_hash.is_some() && row.sanitized_data.is_none() {
+                return Err(missing_blob("snapshot entry", row.config_id));
+            }
+            let (original_content, sanitized_content) = match row.kind {
+                ConfigKind::Text => (
+                    decode_blob(&original_data)?,
+                    row.sanitized_data.as_deref().map(decode_blob).transpose()?,
+                ),
+                ConfigKind::Binary => (String::new(), None),
+            };
+            Ok(SnapshotEntry {
+                snapshot_id: row.snapshot_id,
+                config_id: row.config_id,
+                name: row.name,
+                path: row.path,
+                kind: row.kind,
+                original_content,
+                sanitized_content,
+            })
+        })
+        .collect()
+}
+
+pub async fn delete_snapshot(pool: &SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM snapshot_entries WHERE snapshot_id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM workspace_snapshots WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct SnapshotRestoreRow {
+    config_id: i64,
+    name: String,
+    path: String,
+    kind: ConfigKind,
+    original_hash: String,
+    sanitized_hash: Option<String>,
+    synced_hash: Option<String>,
+    sanitized_output_hash: Option<String>,
+    encoding: Option<String>,
+    has_bom: Option<bool>,
+    line_ending: Option<LineEnding>,
+}
+
+/// Puts every config captured in a snapshot back to its snapshot content and
+/// encoding in a single transaction, recreating configs deleted since with
+/// their sync state. Configs added after the snapshot are left untouched.
+///
+/// A deleted config whose path is now tracked by another config is handled
+/// by `on_collision`; without one, nothing is restored and the colliding
+/// paths are returned.
+pub async fn restore_snapshot(
+    pool: &SqlitePool,
+    snapshot: &WorkspaceSnapshot,
+    on_collision: Option<RestoreCollision>,
+) -> Result<SnapshotRestore, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let entries = sqlx::query_as::<_, SnapshotRestoreRow>(
+        "SELECT config_id, name, path, kind, original_hash, sanitized_hash, synced_hash, sanitized_output_hash,
+                encoding, has_bom, line_ending
+         FROM snapshot_entries WHERE snapshot_id = ?",
+    )
+    .bind(snapshot.id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut restored = 0;
+    let mut collisions = Vec::new();
+    for entry in entries {
+        let mut config_id = entry.config_id;
+        let mut current: Option<(String, Option<String>)> =
+            sqlx::query_as("SELECT original_hash, sanitized_hash FROM configs WHERE id = ?")
+                .bind(config_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        if current.is_none() {
+            let taken: Option<(i64, String, Option<String>)> = sqlx::query_as(
+                "SELECT id, original_hash, sanitized_hash FROM configs WHERE workspace_id = ? AND path = ?",
+            )
+            .bind(snapshot.workspace_id)
+            .bind(&entry.path)
+            .fetch_optional(&mut *tx)
+            .await?;
+            if let Some((id, original, sanitized)) = taken {
+                match on_collision {
+                    None => {
+                        collisions.push(entry.path);
+                        continue;
+                    }
+                    Some(RestoreCollision::Skip) => continue,
+                    Some(RestoreCollision::Replace) => {
+                        config_id = id;
+                        current = Some((original, sanitized));
+                    }
+                }
+            }
+        }
+
+        let (previous_original, previous_sanitized) = match current {
+            Some((original, sanitized)) => {
+                if original == entry.original_hash && sanitized == entry.sanitized_hash {
+                    continue;
+                }
+                // The file on disk is untouched, so the sync state stays
+                sqlx::query(
+                    "UPDATE configs SET original_hash = ?, sanitized_hash = ?, encoding = COALESCE(?, encoding),
+                            has_bom = COALESCE(?, has_bom), line_ending = COALESCE(?, line_ending)
+                     WHERE id = ?",
+                )
+                .bind(&entry.original_hash)
+                .bind(&entry.sanitized_hash)
+                .bind(&entry.encoding)
+                .bind(entry.has_bom)
+                .bind(entry.line_ending)
+                .bind(config_id)
+                .execute(&mut *tx)
+                .await?;
+                (Some(original), sanitized)
+            }
+            None => {
+                sqlx::query(
+                    "INSERT INTO configs (id, workspace_id, name, path, original_content, original_hash, sanitized_hash,
+                                          synced_hash, sanitized_output_hash, encoding, has_bom, line_ending, kind)
+                     VALUES (?, ?, ?, ?, '', ?, ?, ?, ?, COALESCE(?, 'UTF-8'), COALESCE(?, 0), COALESCE(?, 'lf'), ?)",
+                )
+                .bind(config_id)
+                .bind(snapshot.workspace_id)
+                .bind(&entry.name)
+                .bind(&entry.path)
+                .bind(&entry.original_hash)
+                .bind(&entry.sanitized_hash)
+                .bind(&entry.synced_hash)
+                .bind(&entry.sanitized_output_hash)
+                .bind(&entry.encoding)
+                .bind(entry.has_bom)
+                .bind(entry.line_ending)
+                .bind(entry.kind)
+                .execute(&mut *tx)
+                .await?;
+                (None, None)
+            }
+        };
+
+        // Revision history only covers text content
+        if entry.kind == ConfigKind::Binary {
+            restored += 1;
+            continue;
+        }
+        if previous_original.as_ref() != Some(&entry.original_hash) {
+            record_restore(&mut tx, config_id, RevisionField::Original, previous_original, Some(entry.original_hash)).await?;
+        }
+        if previous_sanitized != entry.sanitized_hash {
+            record_restore(&mut tx, config_id, RevisionField::Sanitized, previous_sanitized, entry.sanitized_hash).await?;
+        }
+        restored += 1;
+    }
+
+    if !collisions.is_empty() {
+        tx.rollback().await?;
+        return Ok(SnapshotRestore { restored: 0, collisions });
+    }
+    tx.commit().await?;
+    Ok(SnapshotRestore { restored, collisions })
+}
+
+/// Records a restored value in the revision history, first keeping the value
+/// it replaced when the config had no history for that field yet.
+async fn record_restore(
+    conn: &mut SqliteConnection,
+    config_id: i64,
+    field: RevisionField,
+    previous_hash: Option<String>,
+    content_hash: Option<String>,
+) -> Result<(), sqlx::Error> {
+    let has_history: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM config_revisions WHERE config_id = ? AND field = ?)")
+            .bind(config_id)
+            .bind(field)
+            .fetch_one(&mut *conn)
+            .await?;
+
+    let mut versions = Vec::new();
+    if let (Some(previous_hash), false) = (previous_hash, has_history) {
+        versions.push((RevisionSource::Import, Some(previous_hash)));
+    }
+    versions.push((RevisionSource::Restore, content_hash));
+
+    for (source, content_hash) in versions {
+        sqlx::query("INSERT INTO config_revisions (config_id, field, source, content_hash) VALUES (?, ?, ?, ?)")
+            .bind(config_id)
+            .bind(field)
+            .bind(source)
+            .bind(content_hash)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+// --- File backups ---
+
+/// Backups kept per file; older ones are dropped when a new one is taken.
+const BACKUPS_PER_FILE: i64 = 20;
+
+/// Stores the content a write is about to replace. Content identical to the
+/// file's latest backup is not stored again. Returns the backup id.
+pub async fn add_backup(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    config_id: Option<i64>,
+    workspace_root: &str,
+    path: &str,
+    content: &[u8],
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let hash = put_blob_bytes(&mut *tx, content).await?;
+
+    let latest: Option<(i64, String)> = sqlx::query_as(
+        "SELECT id, content_hash FROM file_backups WHERE workspace_root = ? AND path = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(workspace_root)
+    .bind(path)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if let Some((id, latest_hash)) = latest {
+        if latest_hash == hash {
+            tx.commit().await?;
+            return Ok(id);
+        }
+    }
+
+    let id = sqlx::query(
+        "INSERT INTO file_backups (workspace_id, config_id, workspace_root, path, content_hash, size)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(workspace_id)
+    .bind(config_id)
+    .bind(workspace_root)
+    .bind(path)
+    .bind(&hash)
+    .bind(content.len() as i64)
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    sqlx::query(
+        "DELETE FROM file_backups WHERE workspace_root = ? AND path = ? AND id NOT IN (
+             SELECT id FROM file_backups WHERE workspace_root = ? AND path = ? ORDER BY id DESC LIMIT ?
+         )",
+    )
+    .bind(workspace_root)
+    .bind(path)
+    .bind(workspace_root)
+    .bind(path)
+    .bind(BACKUPS_PER_FILE)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Backups of a workspace's files, newest first. Backups taken before the
+/// workspace or config was removed and added again are matched by root and
+/// path.
+pub async fn get_backups(
+    pool: &SqlitePool,
+    workspace_id: i64,
+    config_id: Option<i64>,
+) -> Result<Vec<FileBackup>, sqlx::Error> {
+    sqlx::query_as::<_, FileBackup>(
+        "SELECT id, workspace_id, config_id, workspace_root, path, content_hash, size, created_at
+         FROM file_backups
+         WHERE (workspace_id = ?1 OR workspace_root = (SELECT root_path FROM workspaces WHERE id = ?1))
+           AND (?2 IS NULL OR config_id = ?2 OR path = (SELECT path FROM configs WHERE id = ?2))
+         ORDER BY id DESC",
+    )
+    .bind(workspace_id)
+    .bind(config_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_backup(pool: &SqlitePool, id: i64) -> Result<Option<FileBackup>, sqlx::Error> {
+    sqlx::query_as::<_, FileBackup>(
+        "SELECT id, workspace_id, config_id, workspace_root, path, content_hash, size, created_at
+         FROM file_backups WHERE id = ?",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+// --- Audit log ---
+
+const AUDIT_COLUMNS: &str = "id, created_at, actor, action, workspace_id, config_id, target_path,
+    hash_before, hash_after, outcome, detail, prev_hash, entry_hash";
+
+/// Appends an entry chained onto the latest one. The write lock is taken up
+/// front so concurrent appends cannot chain onto the same entry.
+pub async fn append_audit_entry(pool: &SqlitePool, record: AuditRecord) -> Result<i64, sqlx::Error> {
+    // BEGIN IMMEDIATE takes the write lock before the chain head is read, so two
+    // appends cannot chain onto the same entry. sqlx 0.7 only issues a deferred
+    // BEGIN, hence the raw statements on a single connection.
+    let mut conn = pool.acquire().await?;
+    sqlx::query("BEGIN IMMEDIATE").execute(&mut *conn).await?;
+    let result = match insert_audit_entry(&mut conn, record).await {
+        Ok(id) => sqlx::query("COMMIT").execute(&mut *conn).await.map(|_| id),
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        let _ = sqlx::query("ROLLBACK").execute(&mut *conn).await;
+    }
+    result
+}
+
+async fn insert_audit_entry(conn: &mut SqliteConnection, record: AuditRecord) -> Result<i64, sqlx::Error> {
+    let prev_hash: Option<String> = sqlx::query_scalar("SELECT entry_hash FROM audit_log ORDER BY id DESC LIMIT 1")
+        .fetch_optional(&mut *conn)
+        .await?;
+    let created_at: String = sqlx::query_scalar("SELECT CURRENT_TIMESTAMP").fetch_one(&mut *conn).await?;
+
+    let entry = record.into_entry(created_at, prev_hash.unwrap_or_else(|| audit::GENESIS_HASH.to_string()));
+    let result = sqlx::query(
+        "INSERT INTO audit_log (created_at, actor, action, workspace_id, config_id, target_path,
+             hash_before, hash_after, outcome, detail, prev_hash, entry_hash)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&entry.created_at)
+    .bind(&entry.actor)
+    .bind(entry.action)
+    .bind(entry.workspace_id)
+    .bind(entry.config_id)
+    .bind(&entry.target_path)
+    .bind(&entry.hash_before)
+    .bind(&entry.hash_after)
+    .bind(entry.outcome)
+    .bind(&entry.detail)
+    .bind(&entry.prev_hash)
+    .bind(&entry.entry_hash)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+/// Entries matching `query`, newest first.
+pub async fn query_audit_log(pool: &SqlitePool, query: &AuditQuery) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    let sql = format!(
+        "SELECT {} FROM audit_log
+         WHERE (?1 IS NULL OR workspace_id = ?1)
+           AND (?2 IS NULL OR config_id = ?2)
+           AND (?3 IS NULL OR action = ?3)
+           AND (?4 IS NULL OR created_at >= ?4)
+           AND (?5 IS NULL OR created_at <= ?5)
+         ORDER BY id DESC LIMIT ?6",
+        AUDIT_COLUMNS
+    );
+    sqlx::query_as::<_, AuditEntry>(&sql)
+        .bind(query.workspace_id)
+        .bind(query.config_id)
+        .bind(query.action)
+        .bind(&query.since)
+        .bind(&query.until)
+        .bind(query.limit.unwrap_or(-1))
+        .fetch_all(pool)
+        .await
+}
+
+/// Every entry in chain order, for verification and export.
+pub async fn get_audit_chain(pool: &SqlitePool) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    sqlx::query_as::<_, AuditEntry>(&format!("SELECT {} FROM audit_log ORDER BY id", AUDIT_COLUMNS))
+        .fetch_all(pool)
+        .await
+}
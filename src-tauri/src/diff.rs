@@ -0,0 +1,158 @@
+// Diff module for comparing two versions of a config
+// Produces structured line hunks with word-level changes for the frontend, and
+// standard unified diff patches for export.
+
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+pub const DEFAULT_CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+impl From<ChangeTag> for LineKind {
+    fn from(tag: ChangeTag) -> Self {
+        match tag {
+            ChangeTag::Equal => LineKind::Equal,
+            ChangeTag::Insert => LineKind::Insert,
+            ChangeTag::Delete => LineKind::Delete,
+        }
+    }
+}
+
+/// Part of a changed line; `changed` marks the words that differ from the
+/// paired line on the other side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InlineSegment {
+    pub changed: bool,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// 1-based line number on the old side, if the line exists there.
+    pub old_line: Option<usize>,
+    /// 1-based line number on the new side, if the line exists there.
+    pub new_line: Option<usize>,
+    pub segments: Vec<InlineSegment>,
+}
+
+/// A hunk with 1-based line ranges, as in a unified diff header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextDiffResult {
+    pub identical: bool,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub hunks: Vec<DiffHunk>,
+}
+
+pub fn diff_text(old: &str, new: &str, context: usize) -> TextDiffResult {
+    let diff = TextDiff::from_lines(old, new);
+    let mut hunks = Vec::new();
+    let (mut insertions, mut deletions) = (0, 0);
+
+    for group in diff.grouped_ops(context) {
+        let (first, last) = match (group.first(), group.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => continue,
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+
+        let mut lines = Vec::new();
+        for op in &group {
+            for change in diff.iter_inline_changes(op) {
+                match change.tag() {
+                    ChangeTag::Insert => insertions += 1,
+                    ChangeTag::Delete => deletions += 1,
+                    ChangeTag::Equal => {}
+                }
+                let segments = change
+                    .iter_strings_lossy()
+                    .map(|(changed, text)| InlineSegment {
+                        changed,
+                        text: text.trim_end_matches(['\r', '\n']).to_string(),
+                    })
+                    .filter(|segment| !segment.text.is_empty())
+                    .collect();
+                lines.push(DiffLine {
+                    kind: change.tag().into(),
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    segments,
+                });
+            }
+        }
+
+        hunks.push(DiffHunk {
+            old_start: old_range.start + 1,
+            old_len: old_range.len(),
+            new_start: new_range.start + 1,
+            new_len: new_range.len(),
+            lines,
+        });
+    }
+
+    TextDiffResult {
+        identical: hunks.is_empty(),
+        insertions,
+        deletions,
+        hunks,
+    }
+}
+
+/// Renders a unified diff patch that `git apply` / `patch` understand.
+pub fn unified_patch(old: &str, new: &str, old_label: &str, new_label: &str, context: usize) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(context)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_text_reports_hunks_and_word_changes() {
+        let old = "a = 1\nhost = \"db.local\"\nc = 3\n";
+        let new = "a = 1\nhost = \"db.prod\"\nc = 3\nd = 4\n";
+
+        let result = diff_text(old, new, 0);
+        assert!(!result.identical);
+        assert_eq!((result.insertions, result.deletions), (2, 1));
+        assert_eq!(result.hunks.len(), 2);
+
+        let hunk = &result.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_len, hunk.new_start, hunk.new_len), (2, 1, 2, 1));
+        let deleted = &hunk.lines[0];
+        assert_eq!(deleted.kind, LineKind::Delete);
+        assert_eq!(deleted.old_line, Some(2));
+        assert!(deleted.segments.iter().any(|s| s.changed && s.text.contains("db.local")));
+        assert!(deleted.segments.iter().any(|s| !s.changed && s.text.starts_with("host")));
+    }
+
+    #[test]
+    fn test_unified_patch_has_standard_headers() {
+        let patch = unified_patch("a\nb\n", "a\nc\n", "a/app.env", "b/app.env", DEFAULT_CONTEXT);
+        assert!(patch.starts_with("--- a/app.env\n+++ b/app.env\n@@ -1,2 +1,2 @@\n"));
+        assert!(patch.contains("-b\n+c\n"));
+        assert!(diff_text("same\n", "same\n", DEFAULT_CONTEXT).identical);
+    }
+}
@@ -0,0 +1,367 @@
+// Discovery module for finding config files in a workspace
+// Walks the workspace root the way git sees it and ranks the files that look
+// like configs by how likely they are to hold secrets.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use ignore::WalkBuilder;
+use serde::Serialize;
+use crate::encoding;
+use crate::file_system::{self, FileSystemError};
+use crate::models::{ConfigKind, ConfigSet};
+
+/// Files larger than this are not configs worth tracking and are not read.
+const MAX_CANDIDATE_SIZE: u64 = 1024 * 1024;
+
+const CONFIG_PATTERNS: &[&str] = &[
+    "*.yaml", "*.yml", "*.toml", "*.json", "*.ini", "*.conf", "*.cfg", "*.properties", "*.xml",
+    "*.config", "*.env", ".env", ".env.*",
+];
+
+/// Generated files that match a config pattern but are never edited by hand.
+const EXCLUDED_PATTERNS: &[&str] = &[
+    "*.lock", "*-lock.json", "*-lock.yaml", "*.lock.json", "tsconfig*.json", "*.min.json", "*.map",
+];
+
+/// Dotenv-style files that are usually git-ignored on purpose but still
+/// belong to a config set.
+const IGNORED_ALLOWLIST: &[&str] = &[".env", ".env.*", "*.env"];
+
+/// Key names whose values are usually credentials.
+const SENSITIVE_KEYS: &[&str] = &[
+    "password", "passwd", "pwd", "secret", "token", "apikey", "api_key", "api-key", "private_key",
+    "privatekey", "access_key", "accesskey", "credential", "dsn", "connection_string", "connectionstring",
+];
+
+/// A file found by a workspace scan that may be worth tracking.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigCandidate {
+    /// Path relative to the workspace root, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub binary: bool,
+    /// The file name suggests credentials (`.env`, `*.pem`, `*secret*`, ...).
+    pub secret_name: bool,
+    /// Lines that assign a value to a key like `password` or `api_key`.
+    pub sensitive_keys: usize,
+    /// Higher is more likely to hold secrets; candidates are sorted by it.
+    pub score: u32,
+    /// Already tracked as a config of the workspace.
+    pub tracked: bool,
+}
+
+/// Walks `workspace_root` and returns the files that look like configs, most
+/// likely to hold secrets first. Files ignored by `.gitignore`, `.ignore` or
+/// `.git/info/exclude` are skipped even when the root is not a git repository.
+/// Unreadable entries are skipped.
+pub fn scan(workspace_root: &str) -> Result<Vec<ConfigCandidate>, FileSystemError> {
+    let mut candidates = Vec::new();
+    for (path, relative_path) in walk_files(workspace_root, true)? {
+        let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+        if size > MAX_CANDIDATE_SIZE || !is_candidate_path(&relative_path) {
+            continue;
+        }
+        let Ok(content) = std::fs::read(&path) else {
+            continue;
+        };
+        candidates.push(rank(relative_path, &content));
+    }
+
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+    Ok(candidates)
+}
+
+/// Relative paths of the files matching a config set's glob, sorted. Ignored
+/// files are skipped like in `scan`, except dotenv-style files, which are
+/// usually git-ignored on purpose; those are still found in every directory
+/// that is not itself ignored.
+pub fn matching_files(workspace_root: &str, pattern: &str) -> Result<Vec<String>, FileSystemError> {
+    let paths = unignored_files(workspace_root)?;
+    Ok(paths.into_iter().filter(|path| file_system::path_matches_glob(pattern, path)).collect())
+}
+
+/// Files not ignored by the workspace's ignore files, plus dotenv-style files
+/// in every directory that is not itself ignored.
+fn unignored_files(workspace_root: &str) -> Result<BTreeSet<String>, FileSystemError> {
+    let mut paths = BTreeSet::new();
+    for (path, relative_path) in walk_entries(workspace_root, true)? {
+        if path.is_file() {
+            paths.insert(relative_path);
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&path) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_file = entry.file_type().is_ok_and(|t| t.is_file());
+            if is_file && IGNORED_ALLOWLIST.iter().any(|allowed| file_system::path_matches_glob(allowed, &name)) {
+                let relative_dir = if relative_path.is_empty() { String::new() } else { format!("{}/", relative_path) };
+                paths.insert(format!("{}{}", relative_dir, name));
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// The set a config belongs to when several sets' patterns match its path:
+/// the most specific pattern wins, i.e. the one anchored to a directory, then
+/// the one with more literal characters, then the older set. Any order of
+/// `sets` gives the same answer.
+pub fn owning_set<'a>(sets: &'a [ConfigSet], relative_path: &str) -> Option<&'a ConfigSet> {
+    sets.iter()
+        .filter(|set| file_system::path_matches_glob(&set.pattern, relative_path))
+        .max_by_key(|set| {
+            let literals = set.pattern.chars().filter(|c| !"*?[]{}!".contains(*c)).count();
+            (set.pattern.contains('/'), literals, std::cmp::Reverse(set.id))
+        })
+}
+
+/// What a missing config's file looked like, to find where it was renamed or
+/// moved to.
+#[derive(Debug)]
+pub struct MovedFileQuery {
+    pub kind: ConfigKind,
+    /// Lowercase extension of the config's path; `None` when it has none.
+    pub extension: Option<String>,
+    /// Sizes the file may have on disk.
+    pub sizes: Vec<u64>,
+    /// Hashes are of the decoded text for text configs and of the raw bytes
+    /// for binary ones, like the stored config hashes.
+    pub hashes: Vec<String>,
+}
+
+/// Files matching `query`, searched like `matching_files`. Only files with
+/// the same extension and one of the sizes are read and hashed.
+pub fn find_by_hash(workspace_root: &str, query: &MovedFileQuery) -> Result<Vec<String>, FileSystemError> {
+    let mut paths = Vec::new();
+    for relative_path in unignored_files(workspace_root)? {
+        if extension(&relative_path) != query.extension {
+            continue;
+        }
+        let path = Path::new(workspace_root).join(&relative_path);
+        if !path.metadata().is_ok_and(|m| query.sizes.contains(&m.len())) {
+            continue;
+        }
+        let Ok(content) = std::fs::read(&path) else {
+            continue;
+        };
+        let hash = match query.kind {
+            ConfigKind::Text => file_system::calculate_content_hash(&encoding::decode(&content).0),
+            ConfigKind::Binary => file_system::calculate_bytes_hash(&content),
+        };
+        if query.hashes.contains(&hash) {
+            paths.push(relative_path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Lowercase extension of a relative path; a leading dot, as in `.env`, does
+/// not start one.
+pub fn extension(relative_path: &str) -> Option<String> {
+    Path::new(relative_path).extension().map(|e| e.to_string_lossy().to_lowercase())
+}
+
+/// Files under the workspace root with their `/`-separated relative paths.
+/// `.git` is never entered and symlinked directories are not followed.
+fn walk_files(
+    workspace_root: &str,
+    respect_ignores: bool,
+) -> Result<impl Iterator<Item = (PathBuf, String)>, FileSystemError> {
+    Ok(walk_entries(workspace_root, respect_ignores)?.filter(|(path, _)| path.is_file()))
+}
+
+/// Files and directories under the workspace root, the root itself included
+/// with an empty relative path.
+fn walk_entries(
+    workspace_root: &str,
+    respect_ignores: bool,
+) -> Result<impl Iterator<Item = (PathBuf, String)>, FileSystemError> {
+    let root = PathBuf::from(workspace_root);
+    if !root.is_dir() {
+        return Err(FileSystemError::InvalidPath("Workspace root is not a directory".to_string()));
+    }
+
+    let walker = WalkBuilder::new(&root)
+        .standard_filters(respect_ignores)
+        .hidden(false)
+        .git_global(false)
+        .require_git(false)
+        .follow_links(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+
+    Ok(walker.flatten().filter_map(move |entry| {
+        let path = entry.into_path();
+        let relative_path = path.strip_prefix(&root).ok()?.to_string_lossy().replace('\\', "/");
+        Some((path, relative_path))
+    }))
+}
+
+/// Whether a path looks like a config file: a config format, or a file whose
+/// name suggests credentials, minus lockfiles and other generated files.
+pub fn is_candidate_path(relative_path: &str) -> bool {
+    let name = file_name(relative_path);
+    if EXCLUDED_PATTERNS.iter().any(|pattern| file_system::path_matches_glob(pattern, &name)) {
+        return false;
+    }
+    CONFIG_PATTERNS.iter().any(|pattern| file_system::path_matches_glob(pattern, &name))
+        || file_system::is_secret_path(Path::new(relative_path))
+}
+
+/// Scores a candidate file. A credential-like name weighs most, then each
+/// sensitive key assignment, then being a config format at all.
+pub fn rank(relative_path: String, content: &[u8]) -> ConfigCandidate {
+    let binary = file_system::is_binary_file(&relative_path, content);
+    let secret_name = file_system::is_secret_path(Path::new(&relative_path));
+    let sensitive_keys = if binary { 0 } else { count_sensitive_keys(&String::from_utf8_lossy(content)) };
+
+    let mut score = 10 + 5 * sensitive_keys.min(10) as u32;
+    if secret_name {
+        score += 50;
+    }
+    ConfigCandidate {
+        size: content.len() as u64,
+        path: relative_path,
+        binary,
+        secret_name,
+        sensitive_keys,
+        score,
+        tracked: false,
+    }
+}
+
+/// Counts `key = value` / `key: value` lines whose key names a credential.
+fn count_sensitive_keys(content: &str) -> usize {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            let end = line.find([':', '='])?;
+            let key = line[..end].trim().trim_matches(['"', '\'']).to_lowercase();
+            let value = line[end + 1..].trim();
+            (!value.is_empty() && !key.starts_with('#')).then_some(key)
+        })
+        .filter(|key| SENSITIVE_KEYS.iter().any(|sensitive| key.contains(sensitive)))
+        .count()
+}
+
+fn file_name(relative_path: &str) -> String {
+    Path::new(relative_path).file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::test_support::TempWorkspace;
+    use std::fs;
+
+    #[test]
+    fn test_scan_respects_gitignore_and_ranks() {
+        let dir = TempWorkspace::new("discovery");
+        fs::create_dir_all(dir.join("services/api")).unwrap();
+        fs::create_dir_all(dir.join("node_modules/pkg")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+
+        fs::write(dir.join(".gitignore"), "node_modules/\n*.local.yaml\n").unwrap();
+        fs::write(dir.join(".env"), "DB_PASSWORD=hunter2\nAPI_KEY=abc\n").unwrap();
+        fs::write(dir.join("services/api/config.yaml"), "database:\n  host: localhost\n  password: x\n").unwrap();
+        fs::write(dir.join("services/api/dev.local.yaml"), "password: x\n").unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+        fs::write(dir.join("Cargo.lock"), "version = 3\n").unwrap();
+        fs::write(dir.join("node_modules/pkg/package.json"), "{\"token\": \"x\"}").unwrap();
+        fs::write(dir.join(".git/config"), "[core]\n").unwrap();
+        fs::write(dir.join("README.md"), "password: see wiki\n").unwrap();
+
+        let candidates = scan(dir.to_str().unwrap()).unwrap();
+        let paths: Vec<&str> = candidates.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec![".env", "services/api/config.yaml", "Cargo.toml"]);
+        assert!(candidates[0].secret_name);
+        assert_eq!(candidates[0].sensitive_keys, 2);
+        assert_eq!(candidates[1].sensitive_keys, 1);
+    }
+
+    #[test]
+    fn test_matching_files_respects_gitignore_but_keeps_dotenv() {
+        let dir = TempWorkspace::new("discovery-set");
+        fs::create_dir_all(dir.join("services/api/config")).unwrap();
+        fs::create_dir_all(dir.join("services/web/config")).unwrap();
+
+        fs::write(dir.join(".gitignore"), "services/web/\n.env*\n*.local.yaml\n").unwrap();
+        fs::write(dir.join("services/api/config/app.yaml"), "a: 1\n").unwrap();
+        fs::write(dir.join("services/api/config/app.local.yaml"), "a: 1\n").unwrap();
+        fs::write(dir.join("services/api/.env.local"), "A=1\n").unwrap();
+        fs::write(dir.join("services/web/config/app.yaml"), "a: 1\n").unwrap();
+        fs::write(dir.join("services/web/.env"), "A=1\n").unwrap();
+        fs::write(dir.join("services/app.yaml"), "a: 1\n").unwrap();
+        fs::write(dir.join(".env"), "A=1\n").unwrap();
+
+        let root = dir.to_str().unwrap();
+        assert_eq!(
+            matching_files(root, "services/*/config/*.yaml").unwrap(),
+            vec!["services/api/config/app.yaml"]
+        );
+        assert_eq!(matching_files(root, "app.yaml").unwrap().len(), 2);
+        assert_eq!(matching_files(root, "/app.yaml").unwrap().len(), 0);
+        assert_eq!(matching_files(root, ".env*").unwrap(), vec![".env", "services/api/.env.local"]);
+        assert_eq!(matching_files(root, "/.env").unwrap(), vec![".env"]);
+    }
+
+    #[test]
+    fn test_owning_set_prefers_the_most_specific_pattern() {
+        let set = |id, pattern: &str| ConfigSet {
+            id,
+            workspace_id: 1,
+            pattern: pattern.to_string(),
+            profile: None,
+            write_mode: crate::models::WriteMode::Direct,
+        };
+        let sets = vec![set(1, "*.yaml"), set(2, "config/*.yaml"), set(3, "config/app.yaml"), set(4, "*.yaml")];
+        let owner = |sets: &[ConfigSet], path| owning_set(sets, path).map(|set| set.id);
+
+        assert_eq!(owner(&sets, "config/app.yaml"), Some(3));
+        assert_eq!(owner(&sets, "config/db.yaml"), Some(2));
+        assert_eq!(owner(&sets, "deploy/db.yaml"), Some(1));
+        assert_eq!(owner(&sets, "app.toml"), None);
+
+        let reversed: Vec<ConfigSet> = sets.iter().rev().cloned().collect();
+        assert_eq!(owner(&reversed, "config/app.yaml"), Some(3));
+        assert_eq!(owner(&reversed, "deploy/db.yaml"), Some(1));
+    }
+
+    #[test]
+    fn test_find_by_hash() {
+        let dir = TempWorkspace::new("discovery-hash");
+        fs::create_dir_all(dir.join("moved")).unwrap();
+        fs::write(dir.join("moved/app.env"), "TOKEN=x\r\n").unwrap();
+        fs::write(dir.join("other.env"), "TOKEN=y\n").unwrap();
+
+        fs::write(dir.join("moved/app.txt"), "TOKEN=x\r\n").unwrap();
+        fs::write(dir.join(".gitignore"), "ignored/\n*.env\n").unwrap();
+        fs::create_dir_all(dir.join("ignored")).unwrap();
+        fs::write(dir.join("ignored/app.conf"), "TOKEN=x\r\n").unwrap();
+
+        let root = dir.to_str().unwrap();
+        let text_hash = file_system::calculate_content_hash("TOKEN=x\n");
+        let query = |kind, extension: &str, sizes: Vec<u64>| MovedFileQuery {
+            kind,
+            extension: Some(extension.to_string()),
+            sizes,
+            hashes: vec![text_hash.clone()],
+        };
+        assert_eq!(find_by_hash(root, &query(ConfigKind::Text, "env", vec![8, 9])).unwrap(), vec!["moved/app.env"]);
+        assert!(find_by_hash(root, &query(ConfigKind::Text, "env", vec![8])).unwrap().is_empty());
+        assert!(find_by_hash(root, &query(ConfigKind::Binary, "env", vec![9])).unwrap().is_empty());
+        assert_eq!(find_by_hash(root, &query(ConfigKind::Text, "txt", vec![9])).unwrap(), vec!["moved/app.txt"]);
+        assert!(find_by_hash(root, &query(ConfigKind::Text, "conf", vec![9])).unwrap().is_empty());
+        assert_eq!(extension(".env"), None);
+        assert_eq!(extension("conf/App.YAML").as_deref(), Some("yaml"));
+    }
+
+    #[test]
+    fn test_count_sensitive_keys() {
+        let content = "# password: none\napi_key = \"abc\"\n\"clientSecret\": \"x\",\ntoken:\nhost: db\n";
+        assert_eq!(count_sensitive_keys(content), 2);
+    }
+}
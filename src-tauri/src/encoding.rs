@@ -0,0 +1,207 @@
+// Encoding module for config files that are not plain UTF-8
+// Detects the character encoding, BOM and line endings of a file so its content
+// can be edited as LF-terminated UTF-8 and written back in the original form.
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    /// Both kinds of line break. Content is kept exactly as it is on disk
+    /// rather than rewritten with one of them.
+    Mixed,
+}
+
+/// How a config file is stored on disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEncoding {
+    /// WHATWG encoding name, e.g. `UTF-8`, `UTF-16LE`, `GBK`, `windows-1252`.
+    pub name: String,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        TextEncoding {
+            name: UTF_8.name().to_string(),
+            bom: false,
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodingError {
+    UnknownEncoding(String),
+    /// The content has characters the target encoding cannot represent.
+    Unmappable(String),
+}
+
+impl std::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodingError::UnknownEncoding(name) => write!(f, "Unknown encoding: {}", name),
+            EncodingError::Unmappable(name) => write!(f, "Content cannot be represented in {}", name),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+/// Decodes file bytes into LF-terminated UTF-8 and reports how they were
+/// stored. A BOM wins; otherwise valid UTF-8 is taken as is and anything else
+/// goes to charset detection. Bytes that still do not decode are replaced.
+/// Mixed line endings are left as they are.
+pub fn decode(bytes: &[u8]) -> (String, TextEncoding) {
+    let (encoding, bom_len) = match Encoding::for_bom(bytes) {
+        Some((encoding, bom_len)) => (encoding, bom_len),
+        None if std::str::from_utf8(bytes).is_ok() => (UTF_8, 0),
+        None => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(bytes, true);
+            (detector.guess(None, true), 0)
+        }
+    };
+
+    let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    let line_ending = detect_line_ending(&text);
+    let content = match line_ending {
+        LineEnding::Crlf => text.replace("\r\n", "\n"),
+        LineEnding::Lf | LineEnding::Mixed => text.into_owned(),
+    };
+
+    let encoding = TextEncoding {
+        name: encoding.name().to_string(),
+        bom: bom_len > 0,
+        line_ending,
+    };
+    (content, encoding)
+}
+
+/// Decodes bytes known to be stored as `encoding`, such as an earlier version
+/// of a tracked file. A BOM still wins, and an unknown encoding falls back to
+/// detection.
+pub fn decode_as(bytes: &[u8], encoding: &TextEncoding) -> String {
+    let Some(known) = Encoding::for_label(encoding.name.as_bytes()) else {
+        return decode(bytes).0;
+    };
+    let (text, _) = match Encoding::for_bom(bytes) {
+        Some((found, bom_len)) => found.decode_without_bom_handling(&bytes[bom_len..]),
+        None => known.decode_without_bom_handling(bytes),
+    };
+    match encoding.line_ending {
+        LineEnding::Crlf => text.replace("\r\n", "\n"),
+        LineEnding::Lf | LineEnding::Mixed => text.into_owned(),
+    }
+}
+
+/// Encodes LF-terminated UTF-8 content back into the given on-disk form.
+pub fn encode(content: &str, encoding: &TextEncoding) -> Result<Vec<u8>, EncodingError> {
+    let target = Encoding::for_label(encoding.name.as_bytes())
+        .ok_or_else(|| EncodingError::UnknownEncoding(encoding.name.clone()))?;
+
+    let text = match encoding.line_ending {
+        LineEnding::Crlf => content.replace("\r\n", "\n").replace('\n', "\r\n"),
+        LineEnding::Lf | LineEnding::Mixed => content.to_string(),
+    };
+
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    // encoding_rs only encodes to UTF-8 for the UTF-16 family, so those are done by hand
+    if target == UTF_16LE || target == UTF_16BE {
+        let little_endian = target == UTF_16LE;
+        let units = std::iter::once(0xFEFF).filter(|_| encoding.bom).chain(text.encode_utf16());
+        for unit in units {
+            let pair = if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() };
+            bytes.extend_from_slice(&pair);
+        }
+        return Ok(bytes);
+    }
+
+    if encoding.bom && target == UTF_8 {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+    let (encoded, _, had_unmappable) = target.encode(&text);
+    if had_unmappable {
+        return Err(EncodingError::Unmappable(encoding.name.clone()));
+    }
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
+
+/// CRLF or LF when every line break is one of them, `Mixed` otherwise.
+pub fn detect_line_ending(text: &str) -> LineEnding {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count();
+    match crlf {
+        0 => LineEnding::Lf,
+        crlf if crlf == lf => LineEnding::Crlf,
+        _ => LineEnding::Mixed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_utf16_bom_crlf() {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "[db]\r\nname=数据库\r\n".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+
+        let (content, encoding) = decode(&bytes);
+        assert_eq!(content, "[db]\nname=数据库\n");
+        assert_eq!(encoding.name, "UTF-16LE");
+        assert!(encoding.bom);
+        assert_eq!(encoding.line_ending, LineEnding::Crlf);
+        assert_eq!(encode(&content, &encoding).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_round_trip_legacy_encoding() {
+        let (bytes, _, _) = encoding_rs::GBK.encode("# 数据库配置\nhost=localhost\nuser=管理员\n");
+        let (content, encoding) = decode(&bytes);
+        assert_eq!(content, "# 数据库配置\nhost=localhost\nuser=管理员\n");
+        assert_eq!(encoding.name, "GBK");
+        assert_eq!(encode(&content, &encoding).unwrap(), bytes.into_owned());
+
+        // A stray invalid byte no longer fails the whole file
+        let (content, encoding) = decode(b"name=caf\xe9\n");
+        assert_eq!(content, "name=café\n");
+        assert_eq!(encode(&content, &encoding).unwrap(), b"name=caf\xe9\n");
+        assert!(matches!(encode("name=数据库\n", &encoding), Err(EncodingError::Unmappable(_))));
+    }
+
+    #[test]
+    fn test_mixed_line_endings_are_kept() {
+        let bytes = b"a=1\r\nb=2\nc=3\r\n";
+        let (content, encoding) = decode(bytes);
+        assert_eq!(encoding.line_ending, LineEnding::Mixed);
+        assert_eq!(content, "a=1\r\nb=2\nc=3\r\n");
+        assert_eq!(encode(&content, &encoding).unwrap(), bytes);
+
+        assert_eq!(detect_line_ending("a\r\nb\r\n"), LineEnding::Crlf);
+        assert_eq!(detect_line_ending("a\nb"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_decode_as_uses_recorded_encoding() {
+        let (bytes, _, _) = encoding_rs::GBK.encode("名=值\r\n");
+        let encoding = TextEncoding {
+            name: "GBK".to_string(),
+            bom: false,
+            line_ending: LineEnding::Crlf,
+        };
+        assert_eq!(decode_as(&bytes, &encoding), "名=值\n");
+
+        let utf8 = TextEncoding::default();
+        assert_eq!(decode_as(b"\xEF\xBB\xBFa=1\n", &utf8), "a=1\n");
+    }
+}
@@ -1,6 +1,18 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::fs;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
+use crate::encoding;
+
+/// Mode for newly created files that look like they hold secrets.
+#[cfg(unix)]
+const SECRET_FILE_MODE: u32 = 0o600;
+#[cfg(unix)]
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Debug)]
 pub enum FileSystemError {
@@ -25,75 +37,567 @@ impl std::fmt::Display for FileSystemError {
 
 impl std::error::Error for FileSystemError {}
 
-pub fn resolve_path(workspace_root: &str, relative_path: &str) -> Result<PathBuf, FileSystemError> {
-    let workspace = Path::new(workspace_root);
-    let full_path = workspace.join(relative_path);
+impl FileSystemError {
+    /// The file, or a directory above it, does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, FileSystemError::IoError(e) if e.kind() == std::io::ErrorKind::NotFound)
+    }
+}
+
+/// What to do when a managed path is a symlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+    /// Read and write the link's target, which must be inside the workspace.
+    #[default]
+    Follow,
+    /// Read the target, but replace the link with a regular file on write.
+    Replace,
+    /// Refuse to read or write through a symlink, including a symlinked
+    /// directory above the file.
+    Refuse,
+}
+
+/// A workspace path resolved once, so the I/O uses the checked location
+/// rather than re-resolving the relative path. A directory swapped for a
+/// symlink after resolution is not detected.
+#[derive(Debug)]
+pub struct ResolvedPath {
+    /// The path itself, with every directory component canonicalized.
+    pub location: PathBuf,
+    /// Canonical target when `location` is a symlink.
+    pub link_target: Option<PathBuf>,
+}
 
+impl ResolvedPath {
+    fn read_path(&self) -> &Path {
+        self.link_target.as_deref().unwrap_or(&self.location)
+    }
+
+    fn write_path(&self, policy: SymlinkPolicy) -> &Path {
+        match policy {
+            SymlinkPolicy::Follow => self.read_path(),
+            SymlinkPolicy::Replace | SymlinkPolicy::Refuse => &self.location,
+        }
+    }
+}
+
+/// Resolves a path inside the workspace. Directory components are checked one
+/// at a time from the workspace root: a symlinked directory is refused under
+/// `Refuse` and otherwise followed, and its target must lie inside the
+/// workspace. The file itself is inspected without following it; a symlink is
+/// then handled per `policy`, and its target must also lie inside the
+/// workspace.
+pub fn resolve_path(
+    workspace_root: &str,
+    relative_path: &str,
+    policy: SymlinkPolicy,
+) -> Result<ResolvedPath, FileSystemError> {
+    resolve(workspace_root, relative_path, policy, false)
+}
+
+/// `resolve_path`, optionally creating missing directories as it goes. Each
+/// one is created only after the directory above it passed the checks.
+fn resolve(
+    workspace_root: &str,
+    relative_path: &str,
+    policy: SymlinkPolicy,
+    create_dirs: bool,
+) -> Result<ResolvedPath, FileSystemError> {
     // Security check: ensure the resolved path is within workspace
-    let canonical_workspace = workspace.canonicalize()
+    let canonical_workspace = Path::new(workspace_root).canonicalize()
         .map_err(|_| FileSystemError::InvalidPath("Invalid workspace path".to_string()))?;
 
-    let canonical_full = full_path.canonicalize()
-        .or_else(|_| {
-            // If file doesn't exist yet, check parent directory
-            if let Some(parent) = full_path.parent() {
-                parent.canonicalize().map(|p| p.join(full_path.file_name().unwrap()))
-            } else {
-                Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Invalid path"))
+    let relative = Path::new(relative_path);
+    let file_name = relative.file_name()
+        .ok_or_else(|| FileSystemError::InvalidPath("Path has no file name".to_string()))?;
+
+    let mut dir = canonical_workspace.clone();
+    for component in relative.parent().into_iter().flat_map(Path::components) {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                dir.pop();
+            }
+            Component::Normal(name) => {
+                dir = resolve_dir(&dir.join(name), &canonical_workspace, policy, create_dirs)?;
             }
-        })
-        .map_err(|_| FileSystemError::InvalidPath("Cannot resolve path".to_string()))?;
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(FileSystemError::InvalidPath("Path is outside workspace".to_string()));
+            }
+        }
+        if !dir.starts_with(&canonical_workspace) {
+            return Err(FileSystemError::InvalidPath("Path is outside workspace".to_string()));
+        }
+    }
+    let location = dir.join(file_name);
+
+    let is_link = fs::symlink_metadata(&location).is_ok_and(|m| m.file_type().is_symlink());
+    if !is_link {
+        return Ok(ResolvedPath { location, link_target: None });
+    }
+    if policy == SymlinkPolicy::Refuse {
+        return Err(FileSystemError::InvalidPath("Path is a symlink".to_string()));
+    }
 
-    if !canonical_full.starts_with(&canonical_workspace) {
+    let link_target = location.canonicalize()?;
+    if !link_target.starts_with(&canonical_workspace) {
         return Err(FileSystemError::InvalidPath(
-            "Path is outside workspace".to_string()
+            "Symlink target is outside workspace".to_string()
         ));
     }
+    Ok(ResolvedPath { location, link_target: Some(link_target) })
+}
+
+/// Checks one directory component below an already resolved directory and
+/// returns where it really is. A missing directory is a `NotFound` error,
+/// e.g. after the directory was moved, unless it is to be created.
+fn resolve_dir(
+    path: &Path,
+    canonical_workspace: &Path,
+    policy: SymlinkPolicy,
+    create: bool,
+) -> Result<PathBuf, FileSystemError> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            if policy == SymlinkPolicy::Refuse {
+                return Err(FileSystemError::InvalidPath("Path has a symlinked directory".to_string()));
+            }
+            let target = path.canonicalize()?;
+            if !target.starts_with(canonical_workspace) {
+                return Err(FileSystemError::InvalidPath(
+                    "Symlinked directory is outside workspace".to_string()
+                ));
+            }
+            Ok(target)
+        }
+        Ok(_) => Ok(path.to_path_buf()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && create => {
+            fs::create_dir(path)?;
+            Ok(path.to_path_buf())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Where a managed path really points when it, or a directory above it, is
+/// a symlink.
+pub fn symlink_target(workspace_root: &str, relative_path: &str) -> Option<PathBuf> {
+    let full_path = Path::new(workspace_root).join(relative_path);
+    match full_path.canonicalize() {
+        Ok(target) => {
+            let unresolved = Path::new(workspace_root).canonicalize().ok()?.join(relative_path);
+            (target != unresolved).then_some(target)
+        }
+        // A dangling link still reports where it points
+        Err(_) => fs::read_link(&full_path).ok(),
+    }
+}
 
-    Ok(full_path)
+/// Reads a text file as LF-terminated UTF-8, whatever its encoding on disk.
+pub fn read_file(workspace_root: &str, relative_path: &str, policy: SymlinkPolicy) -> Result<String, FileSystemError> {
+    let bytes = read_file_bytes(workspace_root, relative_path, policy)?;
+    Ok(encoding::decode(&bytes).0)
 }
 
-pub fn read_file(workspace_root: &str, relative_path: &str) -> Result<String, FileSystemError> {
-    let full_path = resolve_path(workspace_root, relative_path)?;
-    let content = fs::read_to_string(full_path)?;
+pub fn read_file_bytes(
+    workspace_root: &str,
+    relative_path: &str,
+    policy: SymlinkPolicy,
+) -> Result<Vec<u8>, FileSystemError> {
+    let resolved = resolve_path(workspace_root, relative_path, policy)?;
+
+    // The resolved path has no symlink as its last component; refuse to open
+    // one that was swapped in since
+    let mut options = fs::OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+
+    let mut content = Vec::new();
+    options.open(resolved.read_path())?.read_to_end(&mut content)?;
     Ok(content)
 }
 
-pub fn write_file(
+/// Writes atomically: the content goes to a temp file in the target's
+/// directory, is fsynced and then renamed over the target, so a crash leaves
+/// either the old or the new file. Missing directories are created once the
+/// path above them passed the symlink checks. Symlinks are handled per
+/// `policy`, and the existing file's permissions and (on Unix) ownership are
+/// kept.
+///
+/// The temp file is created exclusively and `rename` replaces the directory
+/// entry without following it, so a symlink planted as the file after
+/// resolution is replaced rather than written through.
+pub fn write_file_bytes(
     workspace_root: &str,
     relative_path: &str,
-    content: &str,
+    content: &[u8],
+    policy: SymlinkPolicy,
 ) -> Result<(), FileSystemError> {
-    let full_path = resolve_path(workspace_root, relative_path)?;
+    let resolved = resolve(workspace_root, relative_path, policy, true)?;
+    let target = resolved.write_path(policy);
+
+    let dir = target
+        .parent()
+        .ok_or_else(|| FileSystemError::InvalidPath("Path has no parent directory".to_string()))?;
 
-    // Create parent directories if they don't exist
-    if let Some(parent) = full_path.parent() {
-        fs::create_dir_all(parent)?;
+    // A replaced link gets the permissions of the file it pointed to
+    let existing = fs::metadata(resolved.read_path()).ok();
+    let temp_path = temp_path_for(target);
+    let result = write_temp_file(&temp_path, target, content, existing.as_ref())
+        .and_then(|()| fs::rename(&temp_path, target));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
     }
 
-    fs::write(full_path, content)?;
+    sync_dir(dir);
     Ok(())
 }
 
-pub fn calculate_file_hash(workspace_root: &str, relative_path: &str) -> Result<String, FileSystemError> {
-    let content = read_file(workspace_root, relative_path)?;
-    let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
-    let result = hasher.finalize();
-    Ok(hex::encode(result))
+fn temp_path_for(target: &Path) -> PathBuf {
+    let name = target.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let suffix = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    target.with_file_name(format!(".{}.{}-{}.tmp", name, std::process::id(), suffix))
+}
+
+/// Whether a path is one of the temp files `write_file_bytes` renames over
+/// its target, named `.{name}.{pid}-{n}.tmp`.
+pub fn is_temp_path(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let Some(stem) = name.strip_prefix('.').and_then(|n| n.strip_suffix(".tmp")) else {
+        return false;
+    };
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    stem.rsplit_once('.')
+        .and_then(|(_, id)| id.split_once('-'))
+        .is_some_and(|(pid, n)| is_number(pid) && is_number(n))
+}
+
+fn write_temp_file(
+    temp_path: &Path,
+    target: &Path,
+    content: &[u8],
+    existing: Option<&fs::Metadata>,
+) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        let mode = match existing {
+            Some(metadata) => metadata.permissions().mode() & 0o7777,
+            None if is_secret_path(target) => SECRET_FILE_MODE,
+            None => DEFAULT_FILE_MODE,
+        };
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = target;
+
+    let mut file = options.open(temp_path)?;
+    file.write_all(content)?;
+
+    if let Some(metadata) = existing {
+        // The creation mode is masked by the umask; set the exact original
+        fs::set_permissions(temp_path, metadata.permissions())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            // Only root can give a file away; keep our ownership otherwise
+            let _ = std::os::unix::fs::chown(temp_path, Some(metadata.uid()), Some(metadata.gid()));
+        }
+    }
+
+    file.sync_all()
+}
+
+/// Makes the rename durable. Not every platform can open a directory, so
+/// failures are ignored.
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+/// Whether a file name suggests it holds credentials, in which case a newly
+/// created file is made readable by its owner only.
+pub fn is_secret_path(path: &Path) -> bool {
+    const SECRET_PATTERNS: &[&str] = &[
+        ".env", ".env.*", "*.env", "*.pem", "*.key", "*.p12", "*.pfx", "*.keystore", "*.jks", "id_rsa*",
+        "id_ed25519*", "*secret*", "*credential*", "*password*", ".npmrc", ".pypirc", ".pgpass", ".netrc",
+    ];
+
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    SECRET_PATTERNS.iter().any(|pattern| path_matches_glob(pattern, &name))
+}
+
+/// Hash of the decoded file content, comparable with `calculate_content_hash`
+/// of stored content regardless of the file's encoding and line endings.
+pub fn calculate_file_hash(
+    workspace_root: &str,
+    relative_path: &str,
+    policy: SymlinkPolicy,
+) -> Result<String, FileSystemError> {
+    let content = read_file(workspace_root, relative_path, policy)?;
+    Ok(calculate_content_hash(&content))
+}
+
+/// Whether a file should be kept as opaque bytes: known binary formats, and
+/// anything with a NUL byte near the start that is not BOM-marked UTF-16.
+pub fn is_binary_file(relative_path: &str, content: &[u8]) -> bool {
+    const BINARY_PATTERNS: &[&str] = &[
+        "*.jks", "*.keystore", "*.p12", "*.pfx", "*.der", "*.kdbx", "*.db", "*.sqlite", "*.sqlite3",
+    ];
+    const SNIFF_LEN: usize = 8000;
+
+    let name = Path::new(relative_path).file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if BINARY_PATTERNS.iter().any(|pattern| path_matches_glob(pattern, &name)) {
+        return true;
+    }
+    let has_utf16_bom = content.starts_with(&[0xFF, 0xFE]) || content.starts_with(&[0xFE, 0xFF]);
+    !has_utf16_bom && content.iter().take(SNIFF_LEN).any(|&b| b == 0)
 }
 
 pub fn calculate_content_hash(content: &str) -> String {
+    calculate_bytes_hash(content.as_bytes())
+}
+
+pub fn calculate_bytes_hash(content: &[u8]) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
+    hasher.update(content);
     let result = hasher.finalize();
     hex::encode(result)
 }
 
-pub fn file_exists(workspace_root: &str, relative_path: &str) -> bool {
-    if let Ok(full_path) = resolve_path(workspace_root, relative_path) {
-        full_path.exists()
-    } else {
-        false
+pub fn file_exists(workspace_root: &str, relative_path: &str, policy: SymlinkPolicy) -> bool {
+    resolve_path(workspace_root, relative_path, policy)
+        .is_ok_and(|resolved| fs::symlink_metadata(resolved.read_path()).is_ok())
+}
+
+/// A workspace-relative path the way tracked paths are stored: `/`
+/// separators, no empty or `.` components. `..` is kept, so resolving the
+/// path still rejects escapes.
+pub fn normalize_path(relative_path: &str) -> String {
+    relative_path
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Matches a workspace-relative path against a glob, anchored the way
+/// `.gitignore` entries are: a pattern with a leading or inner `/` matches
+/// from the workspace root, one without matches the file name at any depth.
+pub fn path_matches_glob(pattern: &str, relative_path: &str) -> bool {
+    let normalized = normalize_path(relative_path);
+    let (pattern, target) = match pattern.strip_prefix('/') {
+        Some(anchored) => (anchored, normalized.as_str()),
+        None if pattern.contains('/') => (pattern, normalized.as_str()),
+        None => (pattern, normalized.rsplit('/').next().unwrap_or("")),
+    };
+
+    let options = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    glob::Pattern::new(pattern)
+        .map(|p| p.matches_with(target, options))
+        .unwrap_or(false)
+}
+
+/// Temporary workspace directories for tests in this and other modules.
+#[cfg(test)]
+pub(crate) mod test_support {
+    use std::fs;
+    use std::ops::Deref;
+    use std::path::{Path, PathBuf};
+
+    /// A fresh directory under the system temp dir, removed on drop so a
+    /// failing test does not leave it behind.
+    pub(crate) struct TempWorkspace(PathBuf);
+
+    impl TempWorkspace {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("cfg-fs-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempWorkspace(dir)
+        }
+    }
+
+    impl Deref for TempWorkspace {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempWorkspace {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempWorkspace {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::TempWorkspace;
+    use super::*;
+
+    #[test]
+    fn test_path_matches_glob_anchoring() {
+        assert!(path_matches_glob("app.yaml", "app.yaml"));
+        assert!(path_matches_glob("app.yaml", "deploy/app.yaml"));
+        assert!(path_matches_glob("/app.yaml", "app.yaml"));
+        assert!(!path_matches_glob("/app.yaml", "deploy/app.yaml"));
+        assert!(path_matches_glob("deploy/*.yaml", "deploy/app.yaml"));
+        assert!(!path_matches_glob("deploy/*.yaml", "services/deploy/app.yaml"));
+        assert!(path_matches_glob("**/deploy/*.yaml", "services/deploy/app.yaml"));
+        assert!(path_matches_glob("*.env", "./services\\api.env"));
+    }
+
+    #[test]
+    fn test_is_temp_path() {
+        assert!(is_temp_path(&temp_path_for(Path::new("/srv/app/.env"))));
+        assert!(is_temp_path(Path::new("/srv/app/.app.yaml.4242-7.tmp")));
+        assert!(!is_temp_path(Path::new("/srv/app/.cache.tmp")));
+        assert!(!is_temp_path(Path::new("/srv/app/app.yaml")));
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("./config//app.yaml"), "config/app.yaml");
+        assert_eq!(normalize_path("config\\.\\app.yaml"), "config/app.yaml");
+        assert_eq!(normalize_path("../app.yaml"), "../app.yaml");
+        assert_eq!(normalize_path(".env"), ".env");
+    }
+
+    #[test]
+    fn test_is_binary_file() {
+        assert!(is_binary_file("certs/server.JKS", b"text"));
+        assert!(is_binary_file("seed.bin", b"SQLite format 3\0"));
+        assert!(!is_binary_file("app.ini", &[0xFF, 0xFE, b'a', 0]));
+        assert!(!is_binary_file("app.ini", b"[db]\nhost=x\n"));
+    }
+
+    #[test]
+    fn test_write_replaces_content_without_leaving_temp_files() {
+        let dir = TempWorkspace::new("atomic");
+        let root = dir.to_str().unwrap();
+
+        write_file_bytes(root, "app.yaml", b"a: 1\n", SymlinkPolicy::Follow).unwrap();
+        write_file_bytes(root, "app.yaml", b"a: 2\n", SymlinkPolicy::Follow).unwrap();
+
+        assert_eq!(read_file(root, "app.yaml", SymlinkPolicy::Follow).unwrap(), "a: 2\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_preserves_mode_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempWorkspace::new("mode");
+        let root = dir.to_str().unwrap();
+
+        fs::write(dir.join("db.conf"), "old").unwrap();
+        fs::set_permissions(dir.join("db.conf"), fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink(dir.join("db.conf"), dir.join("link.conf")).unwrap();
+
+        write_file_bytes(root, "link.conf", b"new", SymlinkPolicy::Follow).unwrap();
+        assert!(fs::symlink_metadata(dir.join("link.conf")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(dir.join("db.conf")).unwrap(), "new");
+        assert_eq!(fs::metadata(dir.join("db.conf")).unwrap().permissions().mode() & 0o777, 0o640);
+
+        write_file_bytes(root, ".env.production", b"TOKEN=x", SymlinkPolicy::Follow).unwrap();
+        assert_eq!(fs::metadata(dir.join(".env.production")).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policies() {
+        let dir = TempWorkspace::new("links");
+        let root = dir.to_str().unwrap();
+        let outside = TempWorkspace::new("links-outside");
+
+        fs::create_dir(dir.join("releases")).unwrap();
+        fs::write(dir.join("releases/app.conf"), "v1").unwrap();
+        std::os::unix::fs::symlink(dir.join("releases/app.conf"), dir.join("app.conf")).unwrap();
+        fs::write(outside.join("secret"), "x").unwrap();
+        std::os::unix::fs::symlink(outside.join("secret"), dir.join("escape.conf")).unwrap();
+
+        assert!(read_file(root, "app.conf", SymlinkPolicy::Refuse).is_err());
+        assert!(write_file_bytes(root, "app.conf", b"v2", SymlinkPolicy::Refuse).is_err());
+        assert!(read_file(root, "escape.conf", SymlinkPolicy::Follow).is_err());
+        assert_eq!(symlink_target(root, "app.conf"), Some(dir.join("releases/app.conf")));
+
+        write_file_bytes(root, "app.conf", b"v2", SymlinkPolicy::Replace).unwrap();
+        assert!(!fs::symlink_metadata(dir.join("app.conf")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(dir.join("app.conf")).unwrap(), "v2");
+        assert_eq!(fs::read_to_string(dir.join("releases/app.conf")).unwrap(), "v1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_parent_directory() {
+        let dir = TempWorkspace::new("parent-links");
+        let root = dir.to_str().unwrap();
+        let outside = TempWorkspace::new("parent-links-outside");
+
+        fs::create_dir(dir.join("real")).unwrap();
+        fs::write(dir.join("real/app.conf"), "v1").unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("linked")).unwrap();
+        fs::write(outside.join("secret"), "x").unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("out")).unwrap();
+
+        assert!(read_file(root, "linked/app.conf", SymlinkPolicy::Refuse).is_err());
+        assert!(write_file_bytes(root, "linked/app.conf", b"v2", SymlinkPolicy::Refuse).is_err());
+        assert!(write_file_bytes(root, "linked/new/app.conf", b"v2", SymlinkPolicy::Refuse).is_err());
+        assert!(!dir.join("real/new").exists());
+        assert_eq!(symlink_target(root, "linked/app.conf"), Some(dir.join("real/app.conf")));
+        assert_eq!(symlink_target(root, "real/app.conf"), None);
+
+        assert_eq!(read_file(root, "linked/app.conf", SymlinkPolicy::Follow).unwrap(), "v1");
+        write_file_bytes(root, "linked/app.conf", b"v2", SymlinkPolicy::Follow).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("real/app.conf")).unwrap(), "v2");
+
+        assert!(read_file(root, "out/secret", SymlinkPolicy::Follow).is_err());
+        assert!(write_file_bytes(root, "out/new/secret", b"y", SymlinkPolicy::Replace).is_err());
+        assert!(!outside.join("new").exists());
+    }
+
+    #[test]
+    fn test_missing_directory_is_not_found() {
+        let dir = TempWorkspace::new("missing-dir");
+        let root = dir.to_str().unwrap();
+
+        let err = read_file(root, "gone/app.yaml", SymlinkPolicy::Follow).unwrap_err();
+        assert!(err.is_not_found());
+        assert!(!read_file(root, "../app.yaml", SymlinkPolicy::Follow).unwrap_err().is_not_found());
+
+        assert!(write_file_bytes(root, "../cfg-fs-missing-escape/app.yaml", b"a: 1\n", SymlinkPolicy::Follow).is_err());
+        assert!(!dir.with_file_name("cfg-fs-missing-escape").exists());
+        write_file_bytes(root, "gone/deeper/app.yaml", b"a: 1\n", SymlinkPolicy::Follow).unwrap();
+        assert_eq!(read_file(root, "gone/deeper/app.yaml", SymlinkPolicy::Follow).unwrap(), "a: 1\n");
     }
 }
@@ -1,8 +1,15 @@
 mod models;
 mod db;
+mod blobs;
+mod encoding;
 mod file_system;
 mod sanitizer;
+mod scripting;
 mod merger;
+mod audit;
+mod diff;
+mod discovery;
+mod watcher;
 mod commands;
 
 use std::sync::Mutex;
@@ -21,21 +28,55 @@ pub fn run() {
             let pool = tauri::async_runtime::block_on(db::init_db(&app_data_dir))
                 .expect("Failed to initialize database");
 
+            // Without a watcher the app still works, just without live updates.
+            // Failures are kept for the frontend, which is not listening yet.
+            let mut startup_failures = Vec::new();
+            let watcher = match watcher::WorkspaceWatcher::new(app.handle().clone(), pool.clone()) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    startup_failures.push(watcher::WatchFailure {
+                        workspace_id: None,
+                        path: None,
+                        error: format!("无法启动文件监听: {}", e),
+                    });
+                    None
+                }
+            };
+            if let Some(watcher) = &watcher {
+                let workspaces = tauri::async_runtime::block_on(db::get_all_workspaces(&pool))
+                    .expect("Failed to load workspaces");
+                for workspace in &workspaces {
+                    let dirs = tauri::async_runtime::block_on(watcher::watched_dirs(&pool, workspace.id, &workspace.root_path));
+                    startup_failures.extend(watcher.watch(workspace.id, &workspace.root_path, &dirs));
+                }
+            }
+
             let app_state = AppState {
                 pool: Mutex::new(Some(pool)),
+                watcher: Mutex::new(watcher),
+                startup_failures: Mutex::new(startup_failures),
             };
             app.manage(app_state);
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            commands::take_startup_failures,
             commands::add_workspace,
             commands::get_all_workspaces,
             commands::delete_workspace,
+            commands::set_symlink_policy,
             commands::add_config,
+            commands::scan_workspace,
+            commands::import_configs,
             commands::get_all_configs,
             commands::get_config_by_id,
             commands::check_file_status,
+            commands::find_moved_file,
+            commands::relink_config,
+            commands::recreate_config_file,
             commands::get_config_for_merge,
+            commands::get_semantic_merge,
+            commands::resolve_semantic_merge,
             commands::resolve_conflict,
             commands::update_original_content,
             commands::update_sanitized_content,
@@ -43,8 +84,53 @@ pub fn run() {
             commands::write_to_file_sanitized,
             commands::write_workspace_direct,
             commands::write_workspace_sanitized,
+            commands::pull_binary_config,
+            commands::set_binary_replacement,
             commands::delete_config,
             commands::get_sanitized_preview,
+            commands::add_line_rule,
+            commands::get_line_rules,
+            commands::delete_line_rule,
+            commands::get_available_formats,
+            commands::set_format_order,
+            commands::add_format_override,
+            commands::get_format_overrides,
+            commands::delete_format_override,
+            commands::add_sanitizer_script,
+            commands::get_sanitizer_scripts,
+            commands::delete_sanitizer_script,
+            commands::add_config_set,
+            commands::get_config_sets,
+            commands::delete_config_set,
+            commands::sync_config_sets,
+            commands::write_config_set,
+            commands::create_profile,
+            commands::get_profiles,
+            commands::update_profile,
+            commands::delete_profile,
+            commands::get_profile_override,
+            commands::set_profile_override,
+            commands::get_config_entries,
+            commands::get_key_overrides,
+            commands::set_key_override,
+            commands::delete_key_override,
+            commands::diff_configs,
+            commands::export_diff_patch,
+            commands::get_revisions,
+            commands::get_revision,
+            commands::diff_revision,
+            commands::restore_revision,
+            commands::create_snapshot,
+            commands::get_snapshots,
+            commands::delete_snapshot,
+            commands::diff_snapshot,
+            commands::restore_snapshot,
+            commands::get_audit_log,
+            commands::verify_audit_log,
+            commands::export_audit_log,
+            commands::get_backups,
+            commands::get_backup_content,
+            commands::restore_backup,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
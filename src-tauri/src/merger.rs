@@ -1,5 +1,94 @@
-// Simple merger module for handling content conflicts
-// The actual merge UI will be handled by the frontend using Monaco Editor's diff view
+// Merger module for handling content conflicts
+// Implements a line-level diff3 merge between the last-synced content (base),
+// the database version (ours) and the disk version (theirs). The merge UI is
+// handled by the frontend using Monaco Editor's diff view.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+const OURS_MARKER: &str = "<<<<<<< Database Version";
+const SEPARATOR_MARKER: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>> Disk Version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HunkKind {
+    /// Neither side changed these lines.
+    Unchanged,
+    /// Only the database side changed; its lines were taken.
+    Ours,
+    /// Only the disk side changed; its lines were taken.
+    Theirs,
+    /// Both sides made the same change.
+    Both,
+    /// Both sides changed the same lines differently.
+    Conflict,
+}
+
+/// A line range (0-based start, line count).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineRange {
+    pub start: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeHunk {
+    pub kind: HunkKind,
+    pub base: LineRange,
+    pub ours: LineRange,
+    pub theirs: LineRange,
+    /// Where the hunk ended up in the merged text, including conflict markers.
+    pub merged: LineRange,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeResult {
+    pub merged: String,
+    pub hunks: Vec<MergeHunk>,
+    pub has_conflicts: bool,
+}
+
+/// How a config's database content relates to its file on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    InSync,
+    /// Edited in the app since the last sync; disk is unchanged.
+    LocalAhead,
+    /// Changed on disk since the last sync; the app copy is unchanged.
+    DiskAhead,
+    /// Both sides changed since the last sync.
+    Diverged,
+    MissingOnDisk,
+    /// Disk holds the sanitized output this app last wrote.
+    DiskIsOurSanitizedOutput,
+}
+
+/// Classifies a config from content hashes. Without a recorded sync hash the
+/// side that changed is unknown, so any difference counts as divergence.
+pub fn sync_status(
+    local_hash: &str,
+    synced_hash: Option<&str>,
+    disk_hash: Option<&str>,
+    sanitized_output_hash: Option<&str>,
+) -> SyncStatus {
+    let Some(disk_hash) = disk_hash else {
+        return SyncStatus::MissingOnDisk;
+    };
+    if disk_hash == local_hash {
+        return SyncStatus::InSync;
+    }
+    if sanitized_output_hash == Some(disk_hash) {
+        return SyncStatus::DiskIsOurSanitizedOutput;
+    }
+    match synced_hash {
+        Some(synced) if synced == disk_hash => SyncStatus::LocalAhead,
+        Some(synced) if synced == local_hash => SyncStatus::DiskAhead,
+        _ => SyncStatus::Diverged,
+    }
+}
 
 pub fn detect_conflict(db_content: &str, disk_content: &str) -> bool {
     db_content != disk_content
@@ -7,9 +96,523 @@ pub fn detect_conflict(db_content: &str, disk_content: &str) -> bool {
 
 pub fn generate_conflict_markers(db_content: &str, disk_content: &str) -> String {
     format!(
-        "<<<<<<< Database Version\n{}\n=======\n{}\n>>>>>>> Disk Version\n",
-        db_content, disk_content
+        "{}\n{}\n{}\n{}\n{}\n",
+        OURS_MARKER, db_content, SEPARATOR_MARKER, disk_content, THEIRS_MARKER
+    )
+}
+
+/// Returns the 1-based line numbers of conflict markers left in `content`.
+/// A separator line only counts while a `<<<<<<<` block is open, so plain
+/// `=======` underlines elsewhere in a file are not reported.
+pub fn find_conflict_markers(content: &str) -> Vec<usize> {
+    let is_marker = |line: &str, prefix: &str| {
+        line.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    };
+
+    let mut markers = Vec::new();
+    let mut open = false;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        if is_marker(line, "<<<<<<<") {
+            open = true;
+        } else if is_marker(line, ">>>>>>>") {
+            open = false;
+        } else if !(open && (line == SEPARATOR_MARKER || is_marker(line, "|||||||"))) {
+            continue;
+        }
+        markers.push(i + 1);
+    }
+    markers
+}
+
+/// Splits text into lines, keeping line endings so the pieces join back into
+/// the exact original text.
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// For every base line, the index of the matching line in `other`, if the
+/// line survived unchanged.
+fn match_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        if let DiffOp::Equal { old_index, new_index, len } = op {
+            for i in 0..len {
+                matches[old_index + i] = Some(new_index + i);
+            }
+        }
+    }
+    matches
+}
+
+/// Appends a line, first terminating the previous one if it was the last line
+/// of an input without a trailing newline.
+fn push_line(out: &mut Vec<String>, line: &str) {
+    if let Some(last) = out.last_mut() {
+        if !last.ends_with('\n') {
+            last.push('\n');
+        }
+    }
+    out.push(line.to_string());
+}
+
+fn push_lines(out: &mut Vec<String>, lines: &[&str]) {
+    for line in lines {
+        push_line(out, line);
+    }
+}
+
+/// Three-way merges `ours` and `theirs` against their common ancestor `base`.
+/// Non-overlapping changes are applied automatically; overlapping ones are
+/// wrapped in conflict markers covering only the differing lines.
+pub fn merge_three_way(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let base_lines = split_lines(base);
+    let ours_lines = split_lines(ours);
+    let theirs_lines = split_lines(theirs);
+
+    let ours_matches = match_lines(&base_lines, &ours_lines);
+    let theirs_matches = match_lines(&base_lines, &theirs_lines);
+
+    let mut out: Vec<String> = Vec::new();
+    let mut hunks = Vec::new();
+    let (mut o, mut a, mut b) = (0, 0, 0);
+
+    while o < base_lines.len() || a < ours_lines.len() || b < theirs_lines.len() {
+        // Stable run: the base line is unchanged on both sides
+        let stable_start = o;
+        while o < base_lines.len() && ours_matches[o] == Some(a) && theirs_matches[o] == Some(b) {
+            o += 1;
+            a += 1;
+            b += 1;
+        }
+        if o > stable_start {
+            let len = o - stable_start;
+            let merged_start = out.len();
+            push_lines(&mut out, &base_lines[stable_start..o]);
+            hunks.push(MergeHunk {
+                kind: HunkKind::Unchanged,
+                base: LineRange { start: stable_start, len },
+                ours: LineRange { start: a - len, len },
+                theirs: LineRange { start: b - len, len },
+                merged: LineRange { start: merged_start, len: out.len() - merged_start },
+            });
+            continue;
+        }
+
+        // Unstable run: extend to the next base line both sides kept
+        let mut next_o = o;
+        let (next_a, next_b) = loop {
+            if next_o >= base_lines.len() {
+                break (ours_lines.len(), theirs_lines.len());
+            }
+            if let (Some(na), Some(nb)) = (ours_matches[next_o], theirs_matches[next_o]) {
+                if na >= a && nb >= b {
+                    break (na, nb);
+                }
+            }
+            next_o += 1;
+        };
+
+        let base_chunk = &base_lines[o..next_o];
+        let ours_chunk = &ours_lines[a..next_a];
+        let theirs_chunk = &theirs_lines[b..next_b];
+
+        let kind = if ours_chunk == theirs_chunk {
+            HunkKind::Both
+        } else if ours_chunk == base_chunk {
+            HunkKind::Theirs
+        } else if theirs_chunk == base_chunk {
+            HunkKind::Ours
+        } else {
+            HunkKind::Conflict
+        };
+
+        let merged_start = out.len();
+        match kind {
+            HunkKind::Ours | HunkKind::Both => push_lines(&mut out, ours_chunk),
+            HunkKind::Theirs => push_lines(&mut out, theirs_chunk),
+            HunkKind::Conflict => {
+                push_line(&mut out, &format!("{}\n", OURS_MARKER));
+                push_lines(&mut out, ours_chunk);
+                push_line(&mut out, &format!("{}\n", SEPARATOR_MARKER));
+                push_lines(&mut out, theirs_chunk);
+                push_line(&mut out, &format!("{}\n", THEIRS_MARKER));
+            }
+            HunkKind::Unchanged => unreachable!(),
+        }
+
+        hunks.push(MergeHunk {
+            kind,
+            base: LineRange { start: o, len: next_o - o },
+            ours: LineRange { start: a, len: next_a - a },
+            theirs: LineRange { start: b, len: next_b - b },
+            merged: LineRange { start: merged_start, len: out.len() - merged_start },
+        });
+
+        o = next_o;
+        a = next_a;
+        b = next_b;
+    }
+
+    let merged = out.concat();
+
+    MergeResult {
+        has_conflicts: hunks.iter().any(|h| h.kind == HunkKind::Conflict),
+        merged,
+        hunks,
+    }
+}
+
+// --- Semantic merge ---
+
+#[derive(Debug)]
+pub enum MergeError {
+    UnsupportedFormat(String),
+    ParseError(String),
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::UnsupportedFormat(msg) => write!(f, "Unsupported format: {}", msg),
+            MergeError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticFormat {
+    Json,
+    Yaml,
+    Toml,
+    Env,
+}
+
+impl SemanticFormat {
+    /// Maps a sanitizer name from the registry to a mergeable format.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(SemanticFormat::Json),
+            "yaml" => Some(SemanticFormat::Yaml),
+            "toml" => Some(SemanticFormat::Toml),
+            "env" => Some(SemanticFormat::Env),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictSide {
+    Base,
+    Ours,
+    Theirs,
+}
+
+/// A key changed differently on both sides. Values are rendered as JSON;
+/// `None` means the key is absent on that side.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyConflict {
+    pub key: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SemanticMergeResult {
+    pub merged: String,
+    pub conflicts: Vec<KeyConflict>,
+}
+
+type Tree = serde_json::Value;
+
+fn parse_tree(content: &str, format: SemanticFormat) -> Result<Tree, MergeError> {
+    if content.trim().is_empty() {
+        return Ok(Tree::Object(serde_json::Map::new()));
+    }
+    match format {
+        SemanticFormat::Json => serde_json::from_str(content)
+            .map_err(|e| MergeError::ParseError(format!("Invalid JSON: {}", e))),
+        SemanticFormat::Yaml => serde_yaml::from_str(content)
+            .map_err(|e| MergeError::ParseError(format!("Invalid YAML: {}", e))),
+        SemanticFormat::Toml => {
+            let table: toml::Table = content
+                .parse()
+                .map_err(|e| MergeError::ParseError(format!("Invalid TOML: {}", e)))?;
+            serde_json::to_value(table).map_err(|e| MergeError::ParseError(e.to_string()))
+        }
+        SemanticFormat::Env => Ok(Tree::Object(
+            parse_env_lines(content)
+                .into_iter()
+                .map(|(key, value)| (key, Tree::String(value)))
+                .collect(),
+        )),
+    }
+}
+
+fn parse_env_lines(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn child_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Merges one key path. Objects are merged key by key (so reordering is not a
+/// change); anything else is compared as a whole.
+fn merge_tree(
+    base: Option<&Tree>,
+    ours: Option<&Tree>,
+    theirs: Option<&Tree>,
+    path: &str,
+    resolutions: &HashMap<String, ConflictSide>,
+    conflicts: &mut Vec<KeyConflict>,
+) -> Option<Tree> {
+    if ours == theirs {
+        return ours.cloned();
+    }
+    if base == ours {
+        return theirs.cloned();
+    }
+    if base == theirs {
+        return ours.cloned();
+    }
+
+    if let (Some(Tree::Object(o)), Some(Tree::Object(t))) = (ours, theirs) {
+        let b = match base {
+            Some(Tree::Object(b)) => Some(b),
+            _ => None,
+        };
+        let mut merged = serde_json::Map::new();
+        let keys = o.keys().chain(t.keys().filter(|k| !o.contains_key(*k)));
+        for key in keys {
+            let value = merge_tree(
+                b.and_then(|b| b.get(key)),
+                o.get(key),
+                t.get(key),
+                &child_path(path, key),
+                resolutions,
+                conflicts,
+            );
+            if let Some(value) = value {
+                merged.insert(key.clone(), value);
+            }
+        }
+        return Some(Tree::Object(merged));
+    }
+
+    match resolutions.get(path) {
+        Some(ConflictSide::Base) => base.cloned(),
+        Some(ConflictSide::Ours) => ours.cloned(),
+        Some(ConflictSide::Theirs) => theirs.cloned(),
+        None => {
+            let render = |v: Option<&Tree>| v.map(|v| v.to_string());
+            conflicts.push(KeyConflict {
+                key: path.to_string(),
+                base: render(base),
+                ours: render(ours),
+                theirs: render(theirs),
+            });
+            // Unresolved conflicts keep the local value
+            ours.cloned()
+        }
+    }
+}
+
+/// Detects the indentation unit used by a pretty-printed JSON document.
+fn detect_json_indent(content: &str) -> String {
+    content
+        .lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+        .to_string()
+}
+
+fn render_json(ours: &str, merged: &Tree) -> Result<String, MergeError> {
+    use serde::Serialize as _;
+
+    let indent = detect_json_indent(ours);
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    merged
+        .serialize(&mut serializer)
+        .map_err(|e| MergeError::ParseError(e.to_string()))?;
+
+    let mut rendered = String::from_utf8(buf).map_err(|e| MergeError::ParseError(e.to_string()))?;
+    if ours.ends_with('\n') {
+        rendered.push('\n');
+    }
+    Ok(rendered)
+}
+
+fn tree_to_toml_item(value: &Tree) -> Result<toml_edit::Item, MergeError> {
+    let value: toml::Value =
+        serde_json::from_value(value.clone()).map_err(|e| MergeError::ParseError(e.to_string()))?;
+    match value {
+        toml::Value::Table(table) => {
+            let rendered = toml::to_string(&table).map_err(|e| MergeError::ParseError(e.to_string()))?;
+            let doc = rendered
+                .parse::<toml_edit::DocumentMut>()
+                .map_err(|e| MergeError::ParseError(e.to_string()))?;
+            Ok(toml_edit::Item::Table(doc.as_table().clone()))
+        }
+        other => other
+            .to_string()
+            .parse::<toml_edit::Value>()
+            .map(toml_edit::Item::Value)
+            .map_err(|e| MergeError::ParseError(e.to_string())),
+    }
+}
+
+/// Applies the differences between `ours` and `merged` to a TOML table in
+/// place, leaving untouched keys (and their comments) as they were.
+fn apply_toml_changes(
+    table: &mut dyn toml_edit::TableLike,
+    ours: &serde_json::Map<String, Tree>,
+    merged: &serde_json::Map<String, Tree>,
+) -> Result<(), MergeError> {
+    for key in ours.keys() {
+        if !merged.contains_key(key) {
+            table.remove(key);
+        }
+    }
+    for (key, value) in merged {
+        if ours.get(key) == Some(value) {
+            continue;
+        }
+        if let (Some(Tree::Object(o)), Tree::Object(m)) = (ours.get(key), value) {
+            if let Some(child) = table.get_mut(key).and_then(|item| item.as_table_like_mut()) {
+                apply_toml_changes(child, o, m)?;
+                continue;
+            }
+        }
+        let mut item = tree_to_toml_item(value)?;
+        if let (Some(existing), Some(new_value)) = (table.get(key).and_then(|i| i.as_value()), item.as_value_mut()) {
+            *new_value.decor_mut() = existing.decor().clone();
+        }
+        table.insert(key, item);
+    }
+    Ok(())
+}
+
+fn render_toml(ours: &str, ours_tree: &Tree, merged: &Tree) -> Result<String, MergeError> {
+    let mut doc = ours
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| MergeError::ParseError(format!("Invalid TOML: {}", e)))?;
+    if let (Tree::Object(o), Tree::Object(m)) = (ours_tree, merged) {
+        apply_toml_changes(doc.as_table_mut(), o, m)?;
+    }
+    Ok(doc.to_string())
+}
+
+fn render_env(ours: &str, merged: &Tree) -> String {
+    let empty = serde_json::Map::new();
+    let merged = merged.as_object().unwrap_or(&empty);
+    let as_text = |v: &Tree| match v {
+        Tree::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    let mut lines = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for line in ours.lines() {
+        let trimmed = line.trim();
+        let key = match trimmed.split_once('=') {
+            Some((key, _)) if !trimmed.starts_with('#') => key.trim(),
+            _ => {
+                lines.push(line.to_string());
+                continue;
+            }
+        };
+        seen.insert(key.to_string());
+        // Keys removed by the merge are dropped
+        if let Some(value) = merged.get(key) {
+            let prefix = &line[..line.find('=').unwrap() + 1];
+            lines.push(format!("{}{}", prefix, as_text(value)));
+        }
+    }
+    for (key, value) in merged {
+        if !seen.contains(key) {
+            lines.push(format!("{}={}", key, as_text(value)));
+        }
+    }
+
+    let mut rendered = lines.join("\n");
+    if ours.ends_with('\n') || ours.is_empty() {
+        rendered.push('\n');
+    }
+    rendered
+}
+
+/// Re-serializing YAML would drop comments, key order, anchors and quoting,
+/// so the result is the line-based merge, accepted only when it is clean and
+/// holds the same values as the key-path merge.
+fn render_yaml(base: &str, ours: &str, theirs: &str, merged: &Tree) -> Result<String, MergeError> {
+    let result = merge_three_way(base, ours, theirs);
+    if !result.has_conflicts && parse_tree(&result.merged, SemanticFormat::Yaml).ok().as_ref() == Some(merged) {
+        return Ok(result.merged);
+    }
+    Err(MergeError::UnsupportedFormat(
+        "YAML changes overlap; resolve them with the line-based merge".to_string(),
+    ))
+}
+
+/// Merges per key path instead of per line. Non-conflicting changes from the
+/// disk side are applied onto the local document, which keeps its formatting
+/// for TOML and dotenv; JSON keeps its indentation. YAML is never
+/// re-serialized, see `render_yaml`. Conflicts keep the local value unless a
+/// resolution is given for that path.
+pub fn merge_semantic(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    format: SemanticFormat,
+    resolutions: &HashMap<String, ConflictSide>,
+) -> Result<SemanticMergeResult, MergeError> {
+    let base_tree = parse_tree(base, format)?;
+    let ours_tree = parse_tree(ours, format)?;
+    let theirs_tree = parse_tree(theirs, format)?;
+
+    let mut conflicts = Vec::new();
+    let merged_tree = merge_tree(
+        Some(&base_tree),
+        Some(&ours_tree),
+        Some(&theirs_tree),
+        "",
+        resolutions,
+        &mut conflicts,
     )
+    .unwrap_or_else(|| Tree::Object(serde_json::Map::new()));
+
+    let merged = if merged_tree == ours_tree {
+        ours.to_string()
+    } else {
+        match format {
+            SemanticFormat::Json => render_json(ours, &merged_tree)?,
+            SemanticFormat::Yaml => render_yaml(base, ours, theirs, &merged_tree)?,
+            SemanticFormat::Toml => render_toml(ours, &ours_tree, &merged_tree)?,
+            SemanticFormat::Env => render_env(ours, &merged_tree),
+        }
+    };
+
+    Ok(SemanticMergeResult { merged, conflicts })
 }
 
 #[cfg(test)]
@@ -22,6 +625,17 @@ mod tests {
         assert!(!detect_conflict("same", "same"));
     }
 
+    #[test]
+    fn test_sync_status() {
+        assert_eq!(sync_status("a", Some("a"), Some("a"), None), SyncStatus::InSync);
+        assert_eq!(sync_status("b", Some("a"), Some("a"), None), SyncStatus::LocalAhead);
+        assert_eq!(sync_status("a", Some("a"), Some("b"), None), SyncStatus::DiskAhead);
+        assert_eq!(sync_status("b", Some("a"), Some("c"), None), SyncStatus::Diverged);
+        assert_eq!(sync_status("b", None, Some("c"), None), SyncStatus::Diverged);
+        assert_eq!(sync_status("a", Some("a"), None, None), SyncStatus::MissingOnDisk);
+        assert_eq!(sync_status("a", Some("a"), Some("s"), Some("s")), SyncStatus::DiskIsOurSanitizedOutput);
+    }
+
     #[test]
     fn test_generate_conflict_markers() {
         let result = generate_conflict_markers("db content", "disk content");
@@ -29,4 +643,108 @@ mod tests {
         assert!(result.contains("======="));
         assert!(result.contains(">>>>>>> Disk Version"));
     }
+
+    #[test]
+    fn test_merge_applies_non_overlapping_changes() {
+        let base = "a = 1\nb = 2\nc = 3\nd = 4\n";
+        let ours = "a = 10\nb = 2\nc = 3\nd = 4\n";
+        let theirs = "a = 1\nb = 2\nc = 3\nd = 40\ne = 5\n";
+
+        let result = merge_three_way(base, ours, theirs);
+        assert!(!result.has_conflicts);
+        assert_eq!(result.merged, "a = 10\nb = 2\nc = 3\nd = 40\ne = 5\n");
+        assert_eq!(
+            result.hunks.iter().map(|h| h.kind).collect::<Vec<_>>(),
+            vec![HunkKind::Ours, HunkKind::Unchanged, HunkKind::Theirs]
+        );
+    }
+
+    #[test]
+    fn test_merge_emits_minimal_conflict_region() {
+        let base = "host = a\nport = 1\nuser = x\n";
+        let ours = "host = b\nport = 1\nuser = x\n";
+        let theirs = "host = c\nport = 1\nuser = x\n";
+
+        let result = merge_three_way(base, ours, theirs);
+        assert!(result.has_conflicts);
+        assert_eq!(
+            result.merged,
+            "<<<<<<< Database Version\nhost = b\n=======\nhost = c\n>>>>>>> Disk Version\nport = 1\nuser = x\n"
+        );
+
+        let conflict = &result.hunks[0];
+        assert_eq!(conflict.kind, HunkKind::Conflict);
+        assert_eq!(conflict.base, LineRange { start: 0, len: 1 });
+        assert_eq!(conflict.merged, LineRange { start: 0, len: 5 });
+    }
+
+    #[test]
+    fn test_find_conflict_markers() {
+        let half_resolved = "title\n=======\nhost = b\n<<<<<<< Database Version\nport = 1\n=======\nport = 2\n>>>>>>> Disk Version\n";
+        assert_eq!(find_conflict_markers(half_resolved), vec![4, 6, 8]);
+        assert_eq!(find_conflict_markers(">>>>>>>\r\n"), vec![1]);
+        assert!(find_conflict_markers("title\n=======\n<<<<<<<<< not a marker\n").is_empty());
+    }
+
+    #[test]
+    fn test_semantic_merge_handles_adjacent_keys_and_reordering() {
+        let base = "HOST=a\nPORT=1\n";
+        let ours = "# local\nHOST=b\nPORT=1\n";
+        let theirs = "PORT=2\nHOST=a\nDEBUG=true\n";
+
+        let result = merge_semantic(base, ours, theirs, SemanticFormat::Env, &HashMap::new()).unwrap();
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.merged, "# local\nHOST=b\nPORT=2\nDEBUG=true\n");
+    }
+
+    #[test]
+    fn test_semantic_merge_reports_key_conflicts() {
+        let base = "[db]\nhost = \"a\"\nport = 1\n";
+        let ours = "[db]\nhost = \"b\" # mine\nport = 1\n";
+        let theirs = "[db]\nhost = \"c\"\nport = 2\n";
+
+        let result = merge_semantic(base, ours, theirs, SemanticFormat::Toml, &HashMap::new()).unwrap();
+        assert_eq!(
+            result.conflicts,
+            vec![KeyConflict {
+                key: "db.host".into(),
+                base: Some("\"a\"".into()),
+                ours: Some("\"b\"".into()),
+                theirs: Some("\"c\"".into()),
+            }]
+        );
+        assert_eq!(result.merged, "[db]\nhost = \"b\" # mine\nport = 2\n");
+
+        let resolutions = HashMap::from([("db.host".to_string(), ConflictSide::Theirs)]);
+        let resolved = merge_semantic(base, ours, theirs, SemanticFormat::Toml, &resolutions).unwrap();
+        assert!(resolved.conflicts.is_empty());
+        assert_eq!(resolved.merged, "[db]\nhost = \"c\" # mine\nport = 2\n");
+    }
+
+    #[test]
+    fn test_semantic_merge_keeps_json_indentation() {
+        let base = "{\n    \"a\": 1,\n    \"b\": 2\n}\n";
+        let ours = "{\n    \"a\": 10,\n    \"b\": 2\n}\n";
+        let theirs = "{\"b\": 3, \"a\": 1}";
+
+        let result = merge_semantic(base, ours, theirs, SemanticFormat::Json, &HashMap::new()).unwrap();
+        assert_eq!(result.merged, "{\n    \"a\": 10,\n    \"b\": 3\n}\n");
+    }
+
+    #[test]
+    fn test_semantic_merge_keeps_yaml_formatting_or_refuses() {
+        let base = "# service\ndefaults: &defaults\n  host: a\nport: 1\nname: x\n";
+        let ours = "# service\ndefaults: &defaults\n  host: b # mine\nport: 1\nname: x\n";
+        let theirs = "# service\ndefaults: &defaults\n  host: a\nport: 1\nname: 'y'\n";
+
+        let result = merge_semantic(base, ours, theirs, SemanticFormat::Yaml, &HashMap::new()).unwrap();
+        assert_eq!(result.merged, "# service\ndefaults: &defaults\n  host: b # mine\nport: 1\nname: 'y'\n");
+
+        let resolutions = HashMap::from([("defaults.host".to_string(), ConflictSide::Theirs)]);
+        let conflicting = "# service\ndefaults: &defaults\n  host: c\nport: 1\nname: x\n";
+        assert!(matches!(
+            merge_semantic(base, ours, conflicting, SemanticFormat::Yaml, &resolutions),
+            Err(MergeError::UnsupportedFormat(_))
+        ));
+    }
 }
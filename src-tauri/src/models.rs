@@ -1,11 +1,26 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use crate::diff::TextDiffResult;
+use crate::encoding::TextEncoding;
+use crate::file_system::SymlinkPolicy;
+use crate::merger::{KeyConflict, MergeHunk, SyncStatus};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Workspace {
     pub id: i64,
     pub name: String,
     pub root_path: String,
+    pub symlink_policy: SymlinkPolicy,
+}
+
+/// Text configs are edited and sanitized; binary ones (keystores, databases,
+/// licence files) are kept as opaque bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ConfigKind {
+    Text,
+    Binary,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -14,8 +29,25 @@ pub struct Config {
     pub workspace_id: i64,
     pub name: String,
     pub path: String,
+    pub kind: ConfigKind,
+    /// Empty for binary configs, whose bytes are only kept as a blob.
     pub original_content: String,
     pub sanitized_content: Option<String>,
+    /// On-disk form of the file; contents are stored as LF-terminated UTF-8
+    /// unless the file mixes line endings.
+    pub encoding: TextEncoding,
+}
+
+/// A config without its contents, for list views. Contents are loaded when
+/// the config is opened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigSummary {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub name: String,
+    pub path: String,
+    pub kind: ConfigKind,
+    pub encoding: TextEncoding,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,16 +56,395 @@ pub struct CreateConfig {
     pub name: String,
     pub path: String,
     pub original_content: String,
+    pub encoding: TextEncoding,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A file read from disk for a bulk import.
+#[derive(Debug)]
+pub enum ImportedFile {
+    Text(CreateConfig),
+    Binary {
+        workspace_id: i64,
+        name: String,
+        path: String,
+        content: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Serialize)]
 pub struct FileStatus {
+    pub status: SyncStatus,
+    /// The file changed on disk since the last sync (`disk_ahead` or `diverged`).
     pub has_external_changes: bool,
+    /// Hash of the file on disk; `None` when it is missing.
+    pub current_hash: Option<String>,
+    /// Where the path points when it is a symlink.
+    pub symlink_target: Option<String>,
+}
+
+/// A file that changed on disk since it was last synced, with what the merge
+/// flow needs to reconcile it.
+#[derive(Debug, Serialize)]
+pub struct WriteConflict {
+    pub config_id: i64,
+    pub path: String,
+    pub status: SyncStatus,
+    /// Disk hash the write expected to replace.
+    pub expected_hash: Option<String>,
     pub current_hash: String,
+    /// `None` for binary configs, which cannot be merged.
+    pub merge: Option<MergeData>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Error returned by disk writes. Conflicts are reported separately so the
+/// frontend can open the merge flow instead of showing a message.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WriteError {
+    Conflict { message: String, conflicts: Vec<WriteConflict> },
+    Failed { message: String },
+}
+
+impl From<String> for WriteError {
+    fn from(message: String) -> Self {
+        WriteError::Failed { message }
+    }
+}
+
+impl From<&str> for WriteError {
+    fn from(message: &str) -> Self {
+        WriteError::Failed { message: message.to_string() }
+    }
+}
+
+/// Hashes needed to work out a config's sync status without loading its content.
+#[derive(Debug, Clone, FromRow)]
+pub struct ConfigHashes {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub path: String,
+    pub kind: ConfigKind,
+    pub original_hash: String,
+    pub synced_hash: Option<String>,
+    pub sanitized_output_hash: Option<String>,
+}
+
+impl ConfigHashes {
+    pub fn status(&self, disk_hash: Option<&str>) -> SyncStatus {
+        crate::merger::sync_status(
+            &self.original_hash,
+            self.synced_hash.as_deref(),
+            disk_hash,
+            self.sanitized_output_hash.as_deref(),
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct MergeData {
     pub db_content: String,
     pub disk_content: String,
+    /// Last content synced with disk; `None` for configs added before it was tracked.
+    pub base_content: Option<String>,
+    pub merged_content: String,
+    pub has_conflicts: bool,
+    pub hunks: Vec<MergeHunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct LineRule {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub path_glob: Option<String>,
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FormatOverride {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub path_glob: String,
+    pub format: String,
+    pub priority: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SanitizerScript {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub name: String,
+    pub path_glob: String,
+    pub source: String,
+}
+
+/// How the members of a config set are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum WriteMode {
+    Direct,
+    Sanitized,
+}
+
+/// A path glob within a workspace whose matching files are tracked as
+/// configs. Members are the configs whose path matches and that no more
+/// specific set claims (see `discovery::owning_set`); they are sanitized
+/// with the set's profile unless they have manual sanitized content.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ConfigSet {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub pattern: String,
+    pub profile: Option<String>,
+    pub write_mode: WriteMode,
+}
+
+/// Outcome of matching a config set against the files on disk.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigSetSync {
+    pub set_id: i64,
+    /// Configs imported for newly matching files.
+    pub added: Vec<i64>,
+    /// Members whose file no longer exists.
+    pub missing: Vec<i64>,
+    /// Matching files that could not be read or look like sanitized output.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SanitizeProfile {
+    pub id: i64,
+    pub name: String,
+    #[sqlx(json)]
+    pub reveal_keys: Vec<String>,
+    /// Key globs masked even when a reveal key matches them.
+    #[sqlx(json)]
+    pub mask_keys: Vec<String>,
+    /// Line rules applied on top of the workspace's for formats without a
+    /// structural sanitizer.
+    #[sqlx(json)]
+    pub line_rules: Vec<String>,
+    pub placeholder: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct KeyOverride {
+    pub id: i64,
+    pub config_id: i64,
+    pub key_path: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SemanticMergeData {
+    pub db_content: String,
+    pub disk_content: String,
+    pub base_content: Option<String>,
+    /// Local document with non-conflicting disk changes applied.
+    pub merged_content: String,
+    pub conflicts: Vec<KeyConflict>,
+}
+
+/// One side of a diff. Comparing two configs is done with two `Db` sources.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffSource {
+    Db { config_id: i64 },
+    Disk { config_id: i64, workspace_root: String },
+    Sanitized { config_id: i64, profile: Option<String> },
+    Revision { revision_id: i64 },
+}
+
+/// Which stored content of a config a revision belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum RevisionField {
+    Original,
+    Sanitized,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum RevisionSource {
+    Editor,
+    Merge,
+    Import,
+    DiskPull,
+    Restore,
+}
+
+/// Revision metadata, as listed in the history view.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RevisionSummary {
+    pub id: i64,
+    pub config_id: i64,
+    pub field: RevisionField,
+    pub source: RevisionSource,
+    /// SHA-256 of the content; `None` when the sanitized content was cleared.
+    pub content_hash: Option<String>,
+    pub created_at: String,
+    /// Last autosave folded into this revision.
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigRevision {
+    pub id: i64,
+    pub config_id: i64,
+    pub field: RevisionField,
+    pub source: RevisionSource,
+    pub content: Option<String>,
+    pub content_hash: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigDiff {
+    pub left_label: String,
+    pub right_label: String,
+    pub diff: TextDiffResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WorkspaceSnapshot {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub name: String,
+    pub created_at: String,
+    pub config_count: i64,
+}
+
+/// A config's content as captured in a snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SnapshotEntry {
+    pub snapshot_id: i64,
+    pub config_id: i64,
+    pub name: String,
+    pub path: String,
+    pub kind: ConfigKind,
+    /// Empty for binary configs, as in `Config`.
+    pub original_content: String,
+    pub sanitized_content: Option<String>,
+}
+
+/// How a restore treats a config deleted since the snapshot whose path is
+/// now tracked by another config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreCollision {
+    /// Keep the current config and leave the snapshot entry out.
+    Skip,
+    /// Restore the snapshot content into the config now at that path.
+    Replace,
+}
+
+/// Outcome of a snapshot restore. When paths collide and no resolution was
+/// given, nothing is restored and the colliding paths are returned so the
+/// user can choose one.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotRestore {
+    pub restored: usize,
+    pub collisions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotChange {
+    Unchanged,
+    Modified,
+    /// Config added to the workspace after the snapshot was taken.
+    Added,
+    /// Config deleted since the snapshot was taken.
+    Removed,
+}
+
+/// Snapshot (left) versus current (right) content of one config.
+#[derive(Debug, Serialize)]
+pub struct SnapshotConfigDiff {
+    pub config_id: i64,
+    pub path: String,
+    pub change: SnapshotChange,
+    pub diff: TextDiffResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum AuditAction {
+    WriteDirect,
+    WriteSanitized,
+    ResolveConflict,
+    DeleteWorkspace,
+    DeleteConfig,
+    DeleteSnapshot,
+    RestoreBackup,
+    /// Pointing a config at a different path, e.g. after its file was moved.
+    RelinkConfig,
+    /// Deleting a line rule, format override, script, profile or key override.
+    DeleteRule,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// Recorded before a disk write; the entry that completes it follows.
+    Pending,
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub created_at: String,
+    /// OS user the app was running as.
+    pub actor: String,
+    pub action: AuditAction,
+    pub workspace_id: Option<i64>,
+    pub config_id: Option<i64>,
+    pub target_path: Option<String>,
+    pub hash_before: Option<String>,
+    pub hash_after: Option<String>,
+    pub outcome: AuditOutcome,
+    /// Error message on failure, or extra context such as the profile used.
+    pub detail: Option<String>,
+    pub prev_hash: String,
+    pub entry_hash: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub workspace_id: Option<i64>,
+    pub config_id: Option<i64>,
+    pub action: Option<AuditAction>,
+    /// Inclusive bounds in `YYYY-MM-DD HH:MM:SS` (UTC).
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditVerification {
+    pub valid: bool,
+    pub checked: usize,
+    /// First entry whose hash or link to the previous entry does not match.
+    pub first_broken_id: Option<i64>,
+}
+
+/// Disk content saved before a write replaced it.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FileBackup {
+    pub id: i64,
+    pub workspace_id: i64,
+    /// Config the file belonged to; it may have been deleted since.
+    pub config_id: Option<i64>,
+    pub workspace_root: String,
+    pub path: String,
+    pub content_hash: String,
+    pub size: i64,
+    pub created_at: String,
 }
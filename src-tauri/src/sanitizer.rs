@@ -1,10 +1,16 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
 use serde_json::Value as JsonValue;
 use serde_yaml::Value as YamlValue;
+use regex::Regex;
 
 #[derive(Debug)]
 pub enum SanitizerError {
     UnsupportedFormat(String),
     ParseError(String),
+    InvalidRule(String),
+    ScriptError(String),
 }
 
 impl std::fmt::Display for SanitizerError {
@@ -12,95 +18,619 @@ impl std::fmt::Display for SanitizerError {
         match self {
             SanitizerError::UnsupportedFormat(msg) => write!(f, "Unsupported format: {}", msg),
             SanitizerError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            SanitizerError::InvalidRule(msg) => write!(f, "Invalid rule: {}", msg),
+            SanitizerError::ScriptError(msg) => write!(f, "Script error: {}", msg),
         }
     }
 }
 
 impl std::error::Error for SanitizerError {}
 
-pub fn sanitize_content(content: &str, file_path: &str) -> Result<String, SanitizerError> {
-    let extension = std::path::Path::new(file_path)
+/// A single leaf value found in a config, addressed by its dotted key path
+/// (array elements use their index, e.g. `servers.0.host`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SanitizerEntry {
+    pub key: String,
+    pub value: String,
+}
+
+/// Controls what a sanitizer masks. The default masks every value with `***`.
+#[derive(Debug, Clone)]
+pub struct MaskPolicy {
+    /// Key-path globs whose values are left untouched, matched
+    /// case-insensitively against both the full path (`database.host`) and
+    /// its last segment (`host`).
+    pub reveal_keys: Vec<String>,
+    /// Key-path globs that are masked even when a reveal key matches them,
+    /// matched the same way as `reveal_keys`.
+    pub mask_keys: Vec<String>,
+    /// Replacement for string values and masked spans.
+    pub placeholder: String,
+    /// Fixed output values by key path, applied instead of masking.
+    pub overrides: HashMap<String, String>,
+}
+
+impl Default for MaskPolicy {
+    fn default() -> Self {
+        MaskPolicy {
+            reveal_keys: Vec::new(),
+            mask_keys: Vec::new(),
+            placeholder: "***".to_string(),
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+impl MaskPolicy {
+    /// Reveals every value and only applies `overrides`, for content that is
+    /// already sanitized such as a manual copy.
+    pub fn overrides_only(overrides: HashMap<String, String>) -> Self {
+        MaskPolicy {
+            reveal_keys: vec!["*".to_string()],
+            overrides,
+            ..MaskPolicy::default()
+        }
+    }
+
+    pub fn override_for(&self, key_path: &str) -> Option<&str> {
+        self.overrides.get(key_path).map(String::as_str)
+    }
+
+    pub fn reveals(&self, key_path: &str) -> bool {
+        if key_path.is_empty() {
+            return false;
+        }
+        key_matches(&self.reveal_keys, key_path) && !key_matches(&self.mask_keys, key_path)
+    }
+}
+
+fn key_matches(patterns: &[String], key_path: &str) -> bool {
+    let last_segment = key_path.rsplit('.').next().unwrap_or(key_path);
+    let options = glob::MatchOptions {
+        case_sensitive: false,
+        ..glob::MatchOptions::new()
+    };
+    patterns.iter().any(|pattern| {
+        glob::Pattern::new(pattern)
+            .map(|p| p.matches_with(key_path, options) || p.matches_with(last_segment, options))
+            .unwrap_or(false)
+    })
+}
+
+/// A format the sanitization engine knows how to handle.
+pub trait FormatSanitizer: Send + Sync {
+    /// Stable identifier used for ordering and per-workspace overrides.
+    fn name(&self) -> &str;
+    /// Whether this sanitizer should handle the given file by default.
+    fn detect(&self, file_path: &str, content: &str) -> bool;
+    fn sanitize(&self, content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError>;
+    fn validate(&self, content: &str) -> Result<(), SanitizerError>;
+    fn entries(&self, content: &str) -> Result<Vec<SanitizerEntry>, SanitizerError>;
+}
+
+/// Compiled sanitizers are shared, e.g. scripts cached across renders.
+impl<T: FormatSanitizer + ?Sized> FormatSanitizer for Arc<T> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn detect(&self, file_path: &str, content: &str) -> bool {
+        (**self).detect(file_path, content)
+    }
+
+    fn sanitize(&self, content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
+        (**self).sanitize(content, policy)
+    }
+
+    fn validate(&self, content: &str) -> Result<(), SanitizerError> {
+        (**self).validate(content)
+    }
+
+    fn entries(&self, content: &str) -> Result<Vec<SanitizerEntry>, SanitizerError> {
+        (**self).entries(content)
+    }
+}
+
+/// Whether `name` is taken by a builtin sanitizer, which a script must not shadow.
+pub fn is_builtin_format(name: &str) -> bool {
+    SanitizerRegistry::with_builtins().names().iter().any(|builtin| builtin.eq_ignore_ascii_case(name))
+}
+
+/// Ordered set of sanitizers. Path overrides are checked first, then each
+/// sanitizer's `detect` in registration order.
+pub struct SanitizerRegistry {
+    sanitizers: Vec<Box<dyn FormatSanitizer>>,
+    overrides: Vec<(String, String)>,
+}
+
+impl SanitizerRegistry {
+    pub fn new() -> Self {
+        SanitizerRegistry {
+            sanitizers: Vec::new(),
+            overrides: Vec::new(),
+        }
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut registry = SanitizerRegistry::new();
+        registry.register(Box::new(JsonSanitizer));
+        registry.register(Box::new(YamlSanitizer));
+        registry.register(Box::new(TomlSanitizer));
+        registry.register(Box::new(EnvSanitizer));
+        registry
+    }
+
+    /// Adds a sanitizer with the lowest priority.
+    pub fn register(&mut self, sanitizer: Box<dyn FormatSanitizer>) {
+        self.sanitizers.push(sanitizer);
+    }
+
+    /// Adds a sanitizer with the highest priority.
+    pub fn register_first(&mut self, sanitizer: Box<dyn FormatSanitizer>) {
+        self.sanitizers.insert(0, sanitizer);
+    }
+
+    /// Moves the named sanitizers to the front, in the given order. The rest
+    /// keep their order after them; unknown names are ignored.
+    pub fn reorder(&mut self, names: &[String]) {
+        self.sanitizers
+            .sort_by_key(|s| names.iter().position(|name| name == s.name()).unwrap_or(names.len()));
+    }
+
+    /// Forces files matching `path_glob` to be handled by the named sanitizer,
+    /// regardless of detection. Earlier overrides win.
+    pub fn add_override(&mut self, path_glob: &str, name: &str) -> Result<(), SanitizerError> {
+        if self.get(name).is_none() {
+            return Err(SanitizerError::UnsupportedFormat(format!(
+                "Unknown sanitizer '{}'",
+                name
+            )));
+        }
+        self.overrides.push((path_glob.to_string(), name.to_string()));
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.sanitizers.iter().map(|s| s.name()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn FormatSanitizer> {
+        self.sanitizers
+            .iter()
+            .find(|s| s.name() == name)
+            .map(|s| s.as_ref())
+    }
+
+    pub fn resolve(&self, file_path: &str, content: &str) -> Option<&dyn FormatSanitizer> {
+        for (glob, name) in &self.overrides {
+            if crate::file_system::path_matches_glob(glob, file_path) {
+                return self.get(name);
+            }
+        }
+        self.sanitizers
+            .iter()
+            .find(|s| s.detect(file_path, content))
+            .map(|s| s.as_ref())
+    }
+
+    fn resolve_or_err(&self, file_path: &str, content: &str) -> Result<&dyn FormatSanitizer, SanitizerError> {
+        self.resolve(file_path, content).ok_or_else(|| {
+            SanitizerError::UnsupportedFormat(format!(
+                "File '{}' is not supported for automatic sanitization",
+                file_path
+            ))
+        })
+    }
+
+    pub fn sanitize(&self, content: &str, file_path: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
+        self.resolve_or_err(file_path, content)?.sanitize(content, policy)
+    }
+
+    pub fn validate(&self, content: &str, file_path: &str) -> Result<(), SanitizerError> {
+        self.resolve_or_err(file_path, content)?.validate(content)
+    }
+
+    pub fn entries(&self, content: &str, file_path: &str) -> Result<Vec<SanitizerEntry>, SanitizerError> {
+        self.resolve_or_err(file_path, content)?.entries(content)
+    }
+}
+
+impl Default for SanitizerRegistry {
+    fn default() -> Self {
+        SanitizerRegistry::with_builtins()
+    }
+}
+
+/// Sanitizes `content` with whichever sanitizer in `registry` handles
+/// `file_path`. Line-rule sanitizers are registered last, so unless a
+/// workspace reorders them they only apply when no structural sanitizer
+/// exists.
+pub fn sanitize_content(
+    content: &str,
+    file_path: &str,
+    registry: &SanitizerRegistry,
+    policy: &MaskPolicy,
+) -> Result<String, SanitizerError> {
+    registry.sanitize(content, file_path, policy)
+}
+
+fn file_extension(file_path: &str) -> &str {
+    std::path::Path::new(file_path)
         .extension()
         .and_then(|s| s.to_str())
-        .unwrap_or("");
+        .unwrap_or("")
+}
 
-    match extension {
-        "json" => sanitize_json(content),
-        "yaml" | "yml" => sanitize_yaml(content),
-        "toml" => sanitize_toml(content),
-        "env" => sanitize_env(content),
-        _ => Err(SanitizerError::UnsupportedFormat(format!(
-            "File extension '{}' is not supported for automatic sanitization",
-            extension
-        ))),
+fn child_key(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
     }
 }
 
-fn sanitize_json(content: &str) -> Result<String, SanitizerError> {
+// --- Built-in sanitizers ---
+
+pub struct JsonSanitizer;
+
+impl FormatSanitizer for JsonSanitizer {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn detect(&self, file_path: &str, _content: &str) -> bool {
+        file_extension(file_path) == "json"
+    }
+
+    fn sanitize(&self, content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
+        sanitize_json(content, policy)
+    }
+
+    fn validate(&self, content: &str) -> Result<(), SanitizerError> {
+        serde_json::from_str::<JsonValue>(content)
+            .map(|_| ())
+            .map_err(|e| SanitizerError::ParseError(format!("Invalid JSON: {}", e)))
+    }
+
+    fn entries(&self, content: &str) -> Result<Vec<SanitizerEntry>, SanitizerError> {
+        let value: JsonValue = serde_json::from_str(content)
+            .map_err(|e| SanitizerError::ParseError(format!("Invalid JSON: {}", e)))?;
+        let mut entries = Vec::new();
+        collect_json_entries(&value, "", &mut entries);
+        Ok(entries)
+    }
+}
+
+fn collect_json_entries(value: &JsonValue, path: &str, entries: &mut Vec<SanitizerEntry>) {
+    match value {
+        JsonValue::Object(map) => {
+            for (key, val) in map {
+                collect_json_entries(val, &child_key(path, key), entries);
+            }
+        }
+        JsonValue::Array(arr) => {
+            for (i, val) in arr.iter().enumerate() {
+                collect_json_entries(val, &child_key(path, &i.to_string()), entries);
+            }
+        }
+        JsonValue::String(s) => entries.push(SanitizerEntry {
+            key: path.to_string(),
+            value: s.clone(),
+        }),
+        other => entries.push(SanitizerEntry {
+            key: path.to_string(),
+            value: other.to_string(),
+        }),
+    }
+}
+
+pub struct YamlSanitizer;
+
+impl FormatSanitizer for YamlSanitizer {
+    fn name(&self) -> &str {
+        "yaml"
+    }
+
+    fn detect(&self, file_path: &str, _content: &str) -> bool {
+        matches!(file_extension(file_path), "yaml" | "yml")
+    }
+
+    fn sanitize(&self, content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
+        sanitize_yaml(content, policy)
+    }
+
+    fn validate(&self, content: &str) -> Result<(), SanitizerError> {
+        serde_yaml::from_str::<YamlValue>(content)
+            .map(|_| ())
+            .map_err(|e| SanitizerError::ParseError(format!("Invalid YAML: {}", e)))
+    }
+
+    fn entries(&self, content: &str) -> Result<Vec<SanitizerEntry>, SanitizerError> {
+        let value: YamlValue = serde_yaml::from_str(content)
+            .map_err(|e| SanitizerError::ParseError(format!("Invalid YAML: {}", e)))?;
+        let mut entries = Vec::new();
+        collect_yaml_entries(&value, "", &mut entries);
+        Ok(entries)
+    }
+}
+
+fn yaml_scalar_to_string(value: &YamlValue) -> String {
+    match value {
+        YamlValue::String(s) => s.clone(),
+        YamlValue::Number(n) => n.to_string(),
+        YamlValue::Bool(b) => b.to_string(),
+        YamlValue::Null => "null".to_string(),
+        other => serde_yaml::to_string(other)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn collect_yaml_entries(value: &YamlValue, path: &str, entries: &mut Vec<SanitizerEntry>) {
+    match value {
+        YamlValue::Mapping(map) => {
+            for (key, val) in map {
+                collect_yaml_entries(val, &child_key(path, &yaml_scalar_to_string(key)), entries);
+            }
+        }
+        YamlValue::Sequence(seq) => {
+            for (i, val) in seq.iter().enumerate() {
+                collect_yaml_entries(val, &child_key(path, &i.to_string()), entries);
+            }
+        }
+        YamlValue::Tagged(tagged) => collect_yaml_entries(&tagged.value, path, entries),
+        scalar => entries.push(SanitizerEntry {
+            key: path.to_string(),
+            value: yaml_scalar_to_string(scalar),
+        }),
+    }
+}
+
+pub struct TomlSanitizer;
+
+impl FormatSanitizer for TomlSanitizer {
+    fn name(&self) -> &str {
+        "toml"
+    }
+
+    fn detect(&self, file_path: &str, _content: &str) -> bool {
+        file_extension(file_path) == "toml"
+    }
+
+    fn sanitize(&self, content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
+        sanitize_toml(content, policy)
+    }
+
+    fn validate(&self, content: &str) -> Result<(), SanitizerError> {
+        content
+            .parse::<toml_edit::DocumentMut>()
+            .map(|_| ())
+            .map_err(|e| SanitizerError::ParseError(format!("Invalid TOML: {}", e)))
+    }
+
+    fn entries(&self, content: &str) -> Result<Vec<SanitizerEntry>, SanitizerError> {
+        let table: toml::Table = content
+            .parse()
+            .map_err(|e| SanitizerError::ParseError(format!("Invalid TOML: {}", e)))?;
+        let mut entries = Vec::new();
+        for (key, val) in &table {
+            collect_toml_entries(val, key, &mut entries);
+        }
+        Ok(entries)
+    }
+}
+
+fn collect_toml_entries(value: &toml::Value, path: &str, entries: &mut Vec<SanitizerEntry>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, val) in table {
+                collect_toml_entries(val, &child_key(path, key), entries);
+            }
+        }
+        toml::Value::Array(arr) => {
+            for (i, val) in arr.iter().enumerate() {
+                collect_toml_entries(val, &child_key(path, &i.to_string()), entries);
+            }
+        }
+        toml::Value::String(s) => entries.push(SanitizerEntry {
+            key: path.to_string(),
+            value: s.clone(),
+        }),
+        other => entries.push(SanitizerEntry {
+            key: path.to_string(),
+            value: other.to_string(),
+        }),
+    }
+}
+
+pub struct EnvSanitizer;
+
+impl FormatSanitizer for EnvSanitizer {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn detect(&self, file_path: &str, _content: &str) -> bool {
+        let file_name = std::path::Path::new(file_path)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("");
+        file_extension(file_path) == "env" || file_name == ".env" || file_name.starts_with(".env.")
+    }
+
+    fn sanitize(&self, content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
+        sanitize_env(content, policy)
+    }
+
+    fn validate(&self, _content: &str) -> Result<(), SanitizerError> {
+        // Lines without `=` are preserved as-is, so any text is accepted
+        Ok(())
+    }
+
+    fn entries(&self, content: &str) -> Result<Vec<SanitizerEntry>, SanitizerError> {
+        Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| SanitizerEntry {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+            })
+            .collect())
+    }
+}
+
+/// Fallback for formats without a structural sanitizer, driven by
+/// user-defined regex rules.
+pub struct LineRuleSanitizer {
+    rules: Vec<Regex>,
+}
+
+impl LineRuleSanitizer {
+    pub fn new(patterns: &[String]) -> Result<Self, SanitizerError> {
+        Ok(LineRuleSanitizer {
+            rules: compile_line_rules(patterns)?,
+        })
+    }
+}
+
+impl FormatSanitizer for LineRuleSanitizer {
+    fn name(&self) -> &str {
+        "line-rules"
+    }
+
+    fn detect(&self, _file_path: &str, _content: &str) -> bool {
+        !self.rules.is_empty()
+    }
+
+    fn sanitize(&self, content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
+        sanitize_with_line_rules(content, &self.rules, policy)
+    }
+
+    fn validate(&self, _content: &str) -> Result<(), SanitizerError> {
+        Ok(())
+    }
+
+    fn entries(&self, content: &str) -> Result<Vec<SanitizerEntry>, SanitizerError> {
+        let mut entries = Vec::new();
+        for (line_no, line) in content.lines().enumerate() {
+            for rule in &self.rules {
+                for caps in rule.captures_iter(line) {
+                    let groups: Vec<_> = if caps.len() == 1 {
+                        caps.get(0).into_iter().collect()
+                    } else {
+                        caps.iter().skip(1).flatten().collect()
+                    };
+                    for m in groups {
+                        entries.push(SanitizerEntry {
+                            key: line_key(line).unwrap_or_else(|| (line_no + 1).to_string()),
+                            value: m.as_str().to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn sanitize_json(content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
     let value: JsonValue = serde_json::from_str(content)
         .map_err(|e| SanitizerError::ParseError(format!("Invalid JSON: {}", e)))?;
 
-    let sanitized = sanitize_json_value(&value);
+    let sanitized = sanitize_json_value(&value, "", policy);
 
     serde_json::to_string_pretty(&sanitized)
         .map_err(|e| SanitizerError::ParseError(format!("Failed to serialize JSON: {}", e)))
 }
 
-fn sanitize_json_value(value: &JsonValue) -> JsonValue {
+fn sanitize_json_value(value: &JsonValue, path: &str, policy: &MaskPolicy) -> JsonValue {
+    // Overrides only replace leaves; a container keeps its structure
+    let leaf = !value.is_object() && !value.is_array();
+    if let Some(replacement) = policy.override_for(path).filter(|_| leaf) {
+        // Keep non-string leaves typed when the override parses as that type
+        return match value {
+            JsonValue::String(_) => JsonValue::String(replacement.to_string()),
+            _ => serde_json::from_str(replacement)
+                .unwrap_or_else(|_| JsonValue::String(replacement.to_string())),
+        };
+    }
+    if policy.reveals(path) {
+        return value.clone();
+    }
     match value {
         JsonValue::Object(map) => {
             let mut sanitized_map = serde_json::Map::new();
             for (key, val) in map {
-                sanitized_map.insert(key.clone(), sanitize_json_value(val));
+                sanitized_map.insert(key.clone(), sanitize_json_value(val, &child_key(path, key), policy));
             }
             JsonValue::Object(sanitized_map)
         }
-        JsonValue::Array(arr) => {
-            JsonValue::Array(arr.iter().map(sanitize_json_value).collect())
-        }
-        JsonValue::String(_) => JsonValue::String("***".to_string()),
+        JsonValue::Array(arr) => JsonValue::Array(
+            arr.iter()
+                .enumerate()
+                .map(|(i, val)| sanitize_json_value(val, &child_key(path, &i.to_string()), policy))
+                .collect(),
+        ),
+        JsonValue::String(_) => JsonValue::String(policy.placeholder.clone()),
         JsonValue::Number(_) => JsonValue::Number(serde_json::Number::from(0)),
         JsonValue::Bool(_) => JsonValue::Bool(false),
         JsonValue::Null => JsonValue::Null,
     }
 }
 
-fn sanitize_yaml(content: &str) -> Result<String, SanitizerError> {
+fn sanitize_yaml(content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
     let value: YamlValue = serde_yaml::from_str(content)
         .map_err(|e| SanitizerError::ParseError(format!("Invalid YAML: {}", e)))?;
 
-    let sanitized = sanitize_yaml_value(&value);
+    let sanitized = sanitize_yaml_value(&value, "", policy);
 
     serde_yaml::to_string(&sanitized)
         .map_err(|e| SanitizerError::ParseError(format!("Failed to serialize YAML: {}", e)))
 }
 
-fn sanitize_yaml_value(value: &YamlValue) -> YamlValue {
+fn sanitize_yaml_value(value: &YamlValue, path: &str, policy: &MaskPolicy) -> YamlValue {
+    let leaf = !matches!(value, YamlValue::Mapping(_) | YamlValue::Sequence(_) | YamlValue::Tagged(_));
+    if let Some(replacement) = policy.override_for(path).filter(|_| leaf) {
+        return match value {
+            YamlValue::String(_) => YamlValue::String(replacement.to_string()),
+            _ => serde_yaml::from_str(replacement)
+                .unwrap_or_else(|_| YamlValue::String(replacement.to_string())),
+        };
+    }
+    if policy.reveals(path) {
+        return value.clone();
+    }
     match value {
         YamlValue::Mapping(map) => {
             let mut sanitized_map = serde_yaml::Mapping::new();
             for (key, val) in map {
-                sanitized_map.insert(key.clone(), sanitize_yaml_value(val));
+                let key_path = child_key(path, &yaml_scalar_to_string(key));
+                sanitized_map.insert(key.clone(), sanitize_yaml_value(val, &key_path, policy));
             }
             YamlValue::Mapping(sanitized_map)
         }
-        YamlValue::Sequence(seq) => {
-            YamlValue::Sequence(seq.iter().map(sanitize_yaml_value).collect())
-        }
-        YamlValue::String(_) => YamlValue::String("***".to_string()),
+        YamlValue::Sequence(seq) => YamlValue::Sequence(
+            seq.iter()
+                .enumerate()
+                .map(|(i, val)| sanitize_yaml_value(val, &child_key(path, &i.to_string()), policy))
+                .collect(),
+        ),
+        YamlValue::String(_) => YamlValue::String(policy.placeholder.clone()),
         YamlValue::Number(_) => YamlValue::Number(serde_yaml::Number::from(0)),
         YamlValue::Bool(_) => YamlValue::Bool(false),
         YamlValue::Null => YamlValue::Null,
         YamlValue::Tagged(tagged) => {
             YamlValue::Tagged(Box::new(serde_yaml::value::TaggedValue {
                 tag: tagged.tag.clone(),
-                value: sanitize_yaml_value(&tagged.value),
+                value: sanitize_yaml_value(&tagged.value, path, policy),
             }))
         }
     }
 }
 
-fn sanitize_toml(content: &str) -> Result<String, SanitizerError> {
+fn sanitize_toml(content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
     let mut doc = content
         .parse::<toml_edit::DocumentMut>()
         .map_err(|e| SanitizerError::ParseError(format!("Invalid TOML: {}", e)))?;
@@ -112,31 +642,35 @@ fn sanitize_toml(content: &str) -> Result<String, SanitizerError> {
         .collect();
     for key in keys {
         if let Some(item) = doc.get_mut(&key) {
-            sanitize_toml_item(item);
+            sanitize_toml_item(item, &key, policy);
         }
     }
 
     Ok(doc.to_string())
 }
 
-fn sanitize_toml_item(item: &mut toml_edit::Item) {
+fn sanitize_toml_item(item: &mut toml_edit::Item, path: &str, policy: &MaskPolicy) {
+    // Values handle overrides and reveals themselves; a table is never
+    // replaced by an override
     match item {
-        toml_edit::Item::Value(v) => sanitize_toml_edit_value(v),
+        toml_edit::Item::Value(v) => sanitize_toml_edit_value(v, path, policy),
+        _ if policy.reveals(path) => {}
         toml_edit::Item::Table(t) => {
             let keys: Vec<String> = t.iter().map(|(k, _)| k.to_string()).collect();
             for key in keys {
                 if let Some(child) = t.get_mut(&key) {
-                    sanitize_toml_item(child);
+                    sanitize_toml_item(child, &child_key(path, &key), policy);
                 }
             }
         }
         toml_edit::Item::ArrayOfTables(arr) => {
-            for table in arr.iter_mut() {
+            for (i, table) in arr.iter_mut().enumerate() {
+                let table_path = child_key(path, &i.to_string());
                 let keys: Vec<String> =
                     table.iter().map(|(k, _)| k.to_string()).collect();
                 for key in keys {
                     if let Some(child) = table.get_mut(&key) {
-                        sanitize_toml_item(child);
+                        sanitize_toml_item(child, &child_key(&table_path, &key), policy);
                     }
                 }
             }
@@ -145,11 +679,26 @@ fn sanitize_toml_item(item: &mut toml_edit::Item) {
     }
 }
 
-fn sanitize_toml_edit_value(value: &mut toml_edit::Value) {
+fn sanitize_toml_edit_value(value: &mut toml_edit::Value, path: &str, policy: &MaskPolicy) {
     let decor = value.decor().clone();
+    let leaf = !value.is_array() && !value.is_inline_table();
+    if let Some(replacement) = policy.override_for(path).filter(|_| leaf) {
+        let mut v = match value {
+            toml_edit::Value::String(_) => toml_edit::Value::from(replacement),
+            _ => replacement
+                .parse::<toml_edit::Value>()
+                .unwrap_or_else(|_| toml_edit::Value::from(replacement)),
+        };
+        *v.decor_mut() = decor;
+        *value = v;
+        return;
+    }
+    if policy.reveals(path) {
+        return;
+    }
     let new_val = match value {
         toml_edit::Value::String(_) => {
-            let mut v = toml_edit::Value::from("***");
+            let mut v = toml_edit::Value::from(policy.placeholder.as_str());
             *v.decor_mut() = decor;
             v
         }
@@ -172,7 +721,7 @@ fn sanitize_toml_edit_value(value: &mut toml_edit::Value) {
         toml_edit::Value::Array(arr) => {
             for i in 0..arr.len() {
                 if let Some(elem) = arr.get_mut(i) {
-                    sanitize_toml_edit_value(elem);
+                    sanitize_toml_edit_value(elem, &child_key(path, &i.to_string()), policy);
                 }
             }
             return;
@@ -182,7 +731,7 @@ fn sanitize_toml_edit_value(value: &mut toml_edit::Value) {
                 t.iter().map(|(k, _)| k.to_string()).collect();
             for key in keys {
                 if let Some(child) = t.get_mut(&key) {
-                    sanitize_toml_edit_value(child);
+                    sanitize_toml_edit_value(child, &child_key(path, &key), policy);
                 }
             }
             return;
@@ -191,7 +740,7 @@ fn sanitize_toml_edit_value(value: &mut toml_edit::Value) {
     *value = new_val;
 }
 
-fn sanitize_env(content: &str) -> Result<String, SanitizerError> {
+fn sanitize_env(content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
     let mut sanitized_lines = Vec::new();
 
     for line in content.lines() {
@@ -206,7 +755,13 @@ fn sanitize_env(content: &str) -> Result<String, SanitizerError> {
         // Parse key=value pairs
         if let Some(pos) = trimmed.find('=') {
             let key = &trimmed[..pos];
-            sanitized_lines.push(format!("{}=***", key));
+            if let Some(replacement) = policy.override_for(key.trim()) {
+                sanitized_lines.push(format!("{}={}", key, replacement));
+            } else if policy.reveals(key.trim()) {
+                sanitized_lines.push(line.to_string());
+            } else {
+                sanitized_lines.push(format!("{}={}", key, policy.placeholder));
+            }
         } else {
             // Invalid line, preserve as-is
             sanitized_lines.push(line.to_string());
@@ -216,6 +771,91 @@ fn sanitize_env(content: &str) -> Result<String, SanitizerError> {
     Ok(sanitized_lines.join("\n"))
 }
 
+pub fn compile_line_rules(patterns: &[String]) -> Result<Vec<Regex>, SanitizerError> {
+    patterns
+        .iter()
+        .map(|p| {
+            Regex::new(p).map_err(|e| SanitizerError::InvalidRule(format!("'{}': {}", p, e)))
+        })
+        .collect()
+}
+
+/// Applies regex rules line by line. Every capture group that participates in
+/// a match is replaced with the placeholder; rules without groups mask the
+/// whole match. A line's key (see `line_key`) selects an override as the
+/// replacement, or leaves the line as is when the policy reveals it.
+fn sanitize_with_line_rules(content: &str, rules: &[Regex], policy: &MaskPolicy) -> Result<String, SanitizerError> {
+    let mut sanitized = String::with_capacity(content.len());
+
+    for line in content.split_inclusive('\n') {
+        let body_len = line.trim_end_matches(['\r', '\n']).len();
+        let (body, ending) = line.split_at(body_len);
+
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        for rule in rules {
+            for caps in rule.captures_iter(body) {
+                if caps.len() == 1 {
+                    let m = caps.get(0).unwrap();
+                    spans.push((m.start(), m.end()));
+                    continue;
+                }
+                for m in caps.iter().skip(1).flatten() {
+                    spans.push((m.start(), m.end()));
+                }
+            }
+        }
+
+        let key = line_key(body);
+        match key.as_deref().and_then(|key| policy.override_for(key)) {
+            Some(replacement) => sanitized.push_str(&mask_spans(body, spans, replacement)),
+            None if key.is_some_and(|key| policy.reveals(&key)) => sanitized.push_str(body),
+            None => sanitized.push_str(&mask_spans(body, spans, &policy.placeholder)),
+        }
+        sanitized.push_str(ending);
+    }
+
+    Ok(sanitized)
+}
+
+/// The key a line assigns to, for `key = value`, `key: value` and
+/// `key value` lines, with surrounding quotes removed.
+fn line_key(line: &str) -> Option<String> {
+    let line = line.trim_start();
+    let end = line.find([':', '=', ' ', '\t'])?;
+    let key = line[..end].trim().trim_matches(['"', '\'']);
+    (!key.is_empty() && !key.starts_with('#')).then(|| key.to_string())
+}
+
+/// Replaces the given byte ranges with `placeholder`, merging overlapping ranges.
+fn mask_spans(text: &str, mut spans: Vec<(usize, usize)>, placeholder: &str) -> String {
+    spans.retain(|(start, end)| start < end);
+    spans.sort();
+
+    let mut result = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (start, end) in spans {
+        if end <= cursor {
+            continue;
+        }
+        let start = start.max(cursor);
+        result.push_str(&text[cursor..start]);
+        result.push_str(placeholder);
+        cursor = end;
+    }
+    result.push_str(&text[cursor..]);
+    result
+}
+
+/// Returns the placeholders that occur more often in `incoming` than in
+/// `original`, i.e. values that look masked rather than edited.
+pub fn introduced_placeholders<'a>(original: &str, incoming: &str, placeholders: &[&'a str]) -> Vec<&'a str> {
+    placeholders
+        .iter()
+        .copied()
+        .filter(|p| !p.is_empty() && incoming.matches(p).count() > original.matches(p).count())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,16 +863,27 @@ mod tests {
     #[test]
     fn test_sanitize_json() {
         let input = r#"{"name": "test", "age": 30, "active": true, "data": null}"#;
-        let result = sanitize_json(input).unwrap();
+        let result = sanitize_json(input, &MaskPolicy::default()).unwrap();
         assert!(result.contains(r#""name": "***""#));
         assert!(result.contains(r#""age": 0"#));
         assert!(result.contains(r#""active": false"#));
     }
 
+    #[test]
+    fn test_introduced_placeholders() {
+        let original = "user=admin\npassword=hunter2\nnote=***\n";
+        assert!(introduced_placeholders(original, original, &["***"]).is_empty());
+        assert!(introduced_placeholders(original, "user=admin\npassword=changed\nnote=***\n", &["***"]).is_empty());
+        assert_eq!(
+            introduced_placeholders(original, "user=admin\npassword=***\nnote=***\n", &["***", "<redacted>", ""]),
+            vec!["***"]
+        );
+    }
+
     #[test]
     fn test_sanitize_env() {
         let input = "API_KEY=secret123\n# Comment\nDB_PASSWORD=pass456";
-        let result = sanitize_env(input).unwrap();
+        let result = sanitize_env(input, &MaskPolicy::default()).unwrap();
         assert!(result.contains("API_KEY=***"));
         assert!(result.contains("# Comment"));
         assert!(result.contains("DB_PASSWORD=***"));
@@ -257,7 +908,7 @@ extend-include = ["*.ipynb"]
 name = "tsinghua"
 url = "https://pypi.tuna.tsinghua.edu.cn/simple/"
 "#;
-        let result = sanitize_toml(input).unwrap();
+        let result = sanitize_toml(input, &MaskPolicy::default()).unwrap();
 
         // Comments preserved
         assert!(result.contains("# this is a comment"));
@@ -277,4 +928,157 @@ url = "https://pypi.tuna.tsinghua.edu.cn/simple/"
         assert!(pos_deps < pos_ruff);
         assert!(pos_ruff < pos_source);
     }
+
+    fn registry_with_rules(rules: &[String]) -> Result<SanitizerRegistry, SanitizerError> {
+        let mut registry = SanitizerRegistry::with_builtins();
+        if !rules.is_empty() {
+            registry.register(Box::new(LineRuleSanitizer::new(rules)?));
+        }
+        Ok(registry)
+    }
+
+    #[test]
+    fn test_line_rules_mask_capture_groups() {
+        let input = "bind 127.0.0.1\nrequirepass s3cret\r\n'PASSWORD': 'hunter2',\n";
+        let rules = vec![
+            r"requirepass (\S+)".to_string(),
+            r"'PASSWORD': '([^']*)'".to_string(),
+        ];
+        let registry = registry_with_rules(&rules).unwrap();
+        let result = sanitize_content(input, "redis.conf", &registry, &MaskPolicy::default()).unwrap();
+        assert_eq!(result, "bind 127.0.0.1\nrequirepass ***\r\n'PASSWORD': '***',\n");
+    }
+
+    #[test]
+    fn test_line_rules_only_apply_to_unsupported_formats() {
+        let rules = vec![r"secret".to_string()];
+        let registry = registry_with_rules(&rules).unwrap();
+        let empty = registry_with_rules(&[]).unwrap();
+        let policy = MaskPolicy::default();
+        assert!(sanitize_content("listen 80;", "nginx.conf", &empty, &policy).is_err());
+        assert_eq!(sanitize_content("a secret b", "app.conf", &registry, &policy).unwrap(), "a *** b");
+        assert_eq!(sanitize_content("KEY=secret", "app.env", &registry, &policy).unwrap(), "KEY=***");
+        assert!(matches!(
+            registry_with_rules(&["(".to_string()]),
+            Err(SanitizerError::InvalidRule(_))
+        ));
+    }
+
+    #[test]
+    fn test_registry_detection_and_overrides() {
+        let mut registry = SanitizerRegistry::with_builtins();
+        assert_eq!(registry.resolve(".env.local", "").unwrap().name(), "env");
+        assert!(registry.resolve("app.conf", "").is_none());
+
+        registry.add_override("*.conf", "env").unwrap();
+        assert_eq!(registry.sanitize("PASS=x", "app.conf", &MaskPolicy::default()).unwrap(), "PASS=***");
+        assert!(registry.add_override("*.ini", "ini").is_err());
+    }
+
+    #[test]
+    fn test_registry_reorder() {
+        let mut registry = SanitizerRegistry::with_builtins();
+        registry.register(Box::new(LineRuleSanitizer::new(&["(?i)password=(.*)".to_string()]).unwrap()));
+        assert_eq!(registry.resolve("app.json", "{}").unwrap().name(), "json");
+
+        registry.reorder(&["line-rules".to_string(), "toml".to_string(), "ini".to_string()]);
+        assert_eq!(registry.names(), vec!["line-rules", "toml", "json", "yaml", "env"]);
+        assert_eq!(registry.resolve("app.json", "{}").unwrap().name(), "line-rules");
+
+        registry.add_override("*.json", "json").unwrap();
+        assert_eq!(registry.resolve("app.json", "{}").unwrap().name(), "json");
+    }
+
+    #[test]
+    fn test_entries_use_dotted_key_paths() {
+        let registry = SanitizerRegistry::with_builtins();
+        let entries = registry
+            .entries(r#"{"database": {"host": "db", "ports": [5432]}}"#, "app.json")
+            .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                SanitizerEntry { key: "database.host".into(), value: "db".into() },
+                SanitizerEntry { key: "database.ports.0".into(), value: "5432".into() },
+            ]
+        );
+        assert!(registry.validate("a = ", "app.toml").is_err());
+    }
+
+    #[test]
+    fn test_mask_policy_reveals_keys_and_sets_placeholder() {
+        let policy = MaskPolicy {
+            reveal_keys: vec!["host".to_string(), "*.user*".to_string()],
+            placeholder: "<redacted>".to_string(),
+            ..MaskPolicy::default()
+        };
+        let input = r#"{"database": {"host": "db.local", "username": "app", "password": "pw"}}"#;
+        let result = sanitize_json(input, &policy).unwrap();
+        assert!(result.contains(r#""host": "db.local""#));
+        assert!(result.contains(r#""username": "app""#));
+        assert!(result.contains(r#""password": "<redacted>""#));
+
+        let env = sanitize_env("HOST=db\nPASSWORD=pw", &policy).unwrap();
+        assert_eq!(env, "HOST=db\nPASSWORD=<redacted>");
+    }
+
+    #[test]
+    fn test_mask_keys_win_over_reveal_keys() {
+        let policy = MaskPolicy {
+            reveal_keys: vec!["database.*".to_string()],
+            mask_keys: vec!["*password*".to_string()],
+            ..MaskPolicy::default()
+        };
+        let input = r#"{"database": {"host": "db.local", "password": "pw"}}"#;
+        let result = sanitize_json(input, &policy).unwrap();
+        assert!(result.contains(r#""host": "db.local""#));
+        assert!(result.contains(r#""password": "***""#));
+    }
+
+    #[test]
+    fn test_key_overrides_follow_structure() {
+        let mut policy = MaskPolicy::default();
+        policy.overrides.insert("database.host".into(), "db.example.internal".into());
+        policy.overrides.insert("database.port".into(), "5432".into());
+
+        let json = sanitize_json(r#"{"database": {"host": "10.0.0.5", "port": 6543, "pw": "x"}}"#, &policy).unwrap();
+        assert!(json.contains(r#""host": "db.example.internal""#));
+        assert!(json.contains(r#""port": 5432"#));
+        assert!(json.contains(r#""pw": "***""#));
+
+        let toml = sanitize_toml("[database]\nhost = \"10.0.0.5\" # primary\nport = 6543\n", &policy).unwrap();
+        assert!(toml.contains("host = \"db.example.internal\" # primary"));
+        assert!(toml.contains("port = 5432"));
+    }
+
+    #[test]
+    fn test_key_overrides_only_replace_leaves() {
+        let mut policy = MaskPolicy::default();
+        policy.overrides.insert("database".into(), "gone".into());
+
+        let toml = sanitize_toml("[database]\nhost = \"10.0.0.5\"\n", &policy).unwrap();
+        assert_eq!(toml, "[database]\nhost = \"***\"\n");
+        let json = sanitize_json(r#"{"database": {"host": "10.0.0.5"}}"#, &policy).unwrap();
+        assert!(json.contains(r#""host": "***""#));
+        let yaml = sanitize_yaml("database:\n  host: 10.0.0.5\n", &policy).unwrap();
+        assert_eq!(yaml, "database:\n  host: '***'\n");
+    }
+
+    #[test]
+    fn test_line_rules_apply_overrides_and_reveals_by_line_key() {
+        let rules = vec![r"requirepass (\S+)".to_string(), r"masterauth (\S+)".to_string()];
+        let registry = registry_with_rules(&rules).unwrap();
+        let mut policy = MaskPolicy::default();
+        policy.overrides.insert("requirepass".into(), "changeme".into());
+
+        let input = "requirepass s3cret\nmasterauth hunter2\n";
+        let result = sanitize_content(input, "redis.conf", &registry, &policy).unwrap();
+        assert_eq!(result, "requirepass changeme\nmasterauth ***\n");
+
+        let keys: Vec<String> = registry.entries(input, "redis.conf").unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, vec!["requirepass", "masterauth"]);
+
+        let manual = sanitize_content("requirepass ***\nmasterauth ***\n", "redis.conf", &registry, &MaskPolicy::overrides_only(policy.overrides)).unwrap();
+        assert_eq!(manual, "requirepass changeme\nmasterauth ***\n");
+    }
 }
@@ -0,0 +1,319 @@
+// User-scripted sanitizers backed by Rhai.
+//
+// A script must define `fn mask(content)` returning an array of spans, each
+// either `[start, end]` or `#{ start, end, replacement, key }`. Offsets are
+// character indices into `content` (the same indices Rhai's string functions
+// use). It may also define `fn validate(content)` returning `true`, or a
+// string describing why the content is invalid.
+//
+// The engine has no filesystem, network or module access, and every call is
+// bounded by operation, depth and size limits.
+
+use rhai::{Dynamic, Engine, Scope, AST};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::file_system;
+use crate::models::SanitizerScript;
+use crate::sanitizer::{FormatSanitizer, MaskPolicy, SanitizerEntry, SanitizerError};
+
+const MAX_OPERATIONS: u64 = 5_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 16 * 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 100_000;
+const MAX_MAP_SIZE: usize = 1_000;
+
+struct MaskSpan {
+    start: usize,
+    end: usize,
+    replacement: Option<String>,
+    key: Option<String>,
+}
+
+pub struct ScriptSanitizer {
+    name: String,
+    path_glob: String,
+    engine: Engine,
+    ast: AST,
+}
+
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.on_print(|_| {});
+    engine.on_debug(|_, _, _| {});
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+    engine.set_max_map_size(MAX_MAP_SIZE);
+    engine
+}
+
+impl ScriptSanitizer {
+    pub fn new(name: &str, path_glob: &str, source: &str) -> Result<Self, SanitizerError> {
+        let engine = sandboxed_engine();
+        let ast = engine
+            .compile(source)
+            .map_err(|e| SanitizerError::ScriptError(format!("{}: {}", name, e)))?;
+
+        if !ast.iter_functions().any(|f| f.name == "mask" && f.params.len() == 1) {
+            return Err(SanitizerError::ScriptError(format!(
+                "{}: script must define fn mask(content)",
+                name
+            )));
+        }
+
+        Ok(ScriptSanitizer {
+            name: name.to_string(),
+            path_glob: path_glob.to_string(),
+            engine,
+            ast,
+        })
+    }
+
+    fn call(&self, function: &str, content: &str) -> Result<Dynamic, SanitizerError> {
+        self.engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, function, (content.to_string(),))
+            .map_err(|e| SanitizerError::ScriptError(format!("{}: {}", self.name, e)))
+    }
+
+    fn spans(&self, content: &str) -> Result<Vec<MaskSpan>, SanitizerError> {
+        let result = self.call("mask", content)?;
+        let items = result.into_array().map_err(|t| {
+            SanitizerError::ScriptError(format!("{}: mask() must return an array, got {}", self.name, t))
+        })?;
+
+        let char_count = content.chars().count();
+        let mut spans = Vec::with_capacity(items.len());
+        for item in items {
+            let span = self.parse_span(item)?;
+            if span.start > span.end || span.end > char_count {
+                return Err(SanitizerError::ScriptError(format!(
+                    "{}: span {}..{} is out of range",
+                    self.name, span.start, span.end
+                )));
+            }
+            spans.push(span);
+        }
+        Ok(spans)
+    }
+
+    fn parse_span(&self, item: Dynamic) -> Result<MaskSpan, SanitizerError> {
+        let invalid = || SanitizerError::ScriptError(format!("{}: invalid span returned by mask()", self.name));
+        let as_offset = |d: Option<&Dynamic>| -> Result<usize, SanitizerError> {
+            d.and_then(|v| v.as_int().ok())
+                .and_then(|v| usize::try_from(v).ok())
+                .ok_or_else(invalid)
+        };
+
+        if item.is_array() {
+            let pair = item.into_array().map_err(|_| invalid())?;
+            if pair.len() != 2 {
+                return Err(invalid());
+            }
+            return Ok(MaskSpan {
+                start: as_offset(pair.first())?,
+                end: as_offset(pair.get(1))?,
+                replacement: None,
+                key: None,
+            });
+        }
+
+        let map = item.try_cast::<rhai::Map>().ok_or_else(invalid)?;
+        Ok(MaskSpan {
+            start: as_offset(map.get("start"))?,
+            end: as_offset(map.get("end"))?,
+            replacement: map.get("replacement").map(|v| v.to_string()),
+            key: map.get("key").map(|v| v.to_string()),
+        })
+    }
+}
+
+/// Compiled scripts per workspace by script id, each with the hash of the
+/// name, glob and source it was compiled from, so a render only compiles
+/// scripts that changed.
+#[derive(Default)]
+pub struct ScriptCache {
+    workspaces: Mutex<HashMap<i64, CompiledScripts>>,
+}
+
+/// Script id to the source hash and the script compiled from it.
+type CompiledScripts = HashMap<i64, (String, Arc<ScriptSanitizer>)>;
+
+impl ScriptCache {
+    /// The compiled sanitizers for a workspace's scripts, in the given order.
+    /// Cached scripts that are no longer in `scripts` are dropped.
+    pub fn load(&self, workspace_id: i64, scripts: &[SanitizerScript]) -> Result<Vec<Arc<ScriptSanitizer>>, SanitizerError> {
+        let mut workspaces = self.workspaces.lock().unwrap();
+        let cached = workspaces.entry(workspace_id).or_default();
+        cached.retain(|id, _| scripts.iter().any(|script| script.id == *id));
+
+        scripts
+            .iter()
+            .map(|script| {
+                let hash = file_system::calculate_content_hash(&format!(
+                    "{}\0{}\0{}",
+                    script.name, script.path_glob, script.source
+                ));
+                if let Some((cached_hash, sanitizer)) = cached.get(&script.id) {
+                    if *cached_hash == hash {
+                        return Ok(sanitizer.clone());
+                    }
+                }
+                let sanitizer = Arc::new(ScriptSanitizer::new(&script.name, &script.path_glob, &script.source)?);
+                cached.insert(script.id, (hash, sanitizer.clone()));
+                Ok(sanitizer)
+            })
+            .collect()
+    }
+}
+
+/// Converts character offsets to byte offsets into `content`.
+fn byte_offset(content: &str, char_index: usize) -> usize {
+    content
+        .char_indices()
+        .nth(char_index)
+        .map(|(i, _)| i)
+        .unwrap_or(content.len())
+}
+
+impl FormatSanitizer for ScriptSanitizer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, file_path: &str, _content: &str) -> bool {
+        crate::file_system::path_matches_glob(&self.path_glob, file_path)
+    }
+
+    fn sanitize(&self, content: &str, policy: &MaskPolicy) -> Result<String, SanitizerError> {
+        let mut spans = self.spans(content)?;
+        spans.retain(|s| {
+            !s.key.as_deref().is_some_and(|key| policy.reveals(key) && policy.override_for(key).is_none())
+        });
+        spans.sort_by_key(|s| s.start);
+
+        let mut result = String::with_capacity(content.len());
+        let mut cursor = 0;
+        for span in spans {
+            let start = byte_offset(content, span.start).max(cursor);
+            let end = byte_offset(content, span.end);
+            if end <= cursor {
+                continue;
+            }
+            result.push_str(&content[cursor..start]);
+            let replacement = span
+                .key
+                .as_deref()
+                .and_then(|key| policy.override_for(key))
+                .or(span.replacement.as_deref())
+                .unwrap_or(&policy.placeholder);
+            result.push_str(replacement);
+            cursor = end;
+        }
+        result.push_str(&content[cursor..]);
+        Ok(result)
+    }
+
+    fn validate(&self, content: &str) -> Result<(), SanitizerError> {
+        if !self.ast.iter_functions().any(|f| f.name == "validate" && f.params.len() == 1) {
+            return Ok(());
+        }
+
+        let result = self.call("validate", content)?;
+        match result.as_bool() {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(SanitizerError::ParseError(format!("{}: validation failed", self.name))),
+            Err(_) => Err(SanitizerError::ParseError(format!("{}: {}", self.name, result))),
+        }
+    }
+
+    fn entries(&self, content: &str) -> Result<Vec<SanitizerEntry>, SanitizerError> {
+        Ok(self
+            .spans(content)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, span)| {
+                let start = byte_offset(content, span.start);
+                let end = byte_offset(content, span.end);
+                SanitizerEntry {
+                    key: span.key.unwrap_or_else(|| i.to_string()),
+                    value: content[start..end].to_string(),
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KV_SCRIPT: &str = r#"
+        fn mask(content) {
+            let spans = [];
+            let offset = 0;
+            for line in content.split("\n") {
+                let pos = line.index_of(":=");
+                if pos >= 0 {
+                    spans.push(#{ start: offset + pos + 2, end: offset + line.len(), key: line.sub_string(0, pos) });
+                }
+                offset += line.len() + 1;
+            }
+            spans
+        }
+    "#;
+
+    #[test]
+    fn test_script_masks_returned_spans() {
+        let script = ScriptSanitizer::new("kv", "*.cfg", KV_SCRIPT).unwrap();
+        assert!(script.detect("server/game.cfg", ""));
+
+        let input = "name:=Über\nrcon:=hunter2\n# note";
+        assert_eq!(script.sanitize(input, &MaskPolicy::default()).unwrap(), "name:=***\nrcon:=***\n# note");
+
+        let entries = script.entries(input).unwrap();
+        assert_eq!(entries[1].key, "rcon");
+        assert_eq!(entries[1].value, "hunter2");
+    }
+
+    #[test]
+    fn test_cache_recompiles_changed_scripts() {
+        let script = |id, source: &str| SanitizerScript {
+            id,
+            workspace_id: 1,
+            name: format!("kv{}", id),
+            path_glob: "*.cfg".to_string(),
+            source: source.to_string(),
+        };
+        let cache = ScriptCache::default();
+
+        let first = cache.load(1, &[script(1, KV_SCRIPT), script(2, KV_SCRIPT)]).unwrap();
+        let again = cache.load(1, &[script(1, KV_SCRIPT), script(2, KV_SCRIPT)]).unwrap();
+        assert!(Arc::ptr_eq(&first[0], &again[0]));
+
+        let edited = cache.load(1, &[script(1, "fn mask(c) { [] }")]).unwrap();
+        assert!(!Arc::ptr_eq(&first[0], &edited[0]));
+        assert_eq!(edited[0].sanitize("a:=b", &MaskPolicy::default()).unwrap(), "a:=b");
+        assert_eq!(cache.workspaces.lock().unwrap()[&1].len(), 1);
+    }
+
+    #[test]
+    fn test_script_errors_are_reported() {
+        assert!(matches!(
+            ScriptSanitizer::new("bad", "*", "fn other(x) { x }"),
+            Err(SanitizerError::ScriptError(_))
+        ));
+
+        let looping = ScriptSanitizer::new("loop", "*", "fn mask(c) { loop {} }").unwrap();
+        assert!(matches!(looping.sanitize("x", &MaskPolicy::default()), Err(SanitizerError::ScriptError(_))));
+
+        let importing = ScriptSanitizer::new("import", "*", r#"fn mask(c) { import "fs" as fs; [] }"#).unwrap();
+        assert!(importing.sanitize("x", &MaskPolicy::default()).is_err());
+
+        let out_of_range = ScriptSanitizer::new("range", "*", "fn mask(c) { [[0, 100]] }").unwrap();
+        assert!(out_of_range.sanitize("short", &MaskPolicy::default()).is_err());
+    }
+}
@@ -0,0 +1,497 @@
+// Watcher module for live drift detection
+// Watches the directories holding a workspace's configs and config set matches,
+// debounces filesystem events and emits `config-file-changed` for each change
+// to a managed config path, so the frontend does not have to poll
+// `check_file_status`. Files appearing or disappearing under a config set's
+// glob resync the set (`config-set-synced`); a set's base directory is watched
+// recursively where its glob reaches into subdirectories, so new ones are
+// seen too. Directories that cannot be watched are reported with `watch-failed`.
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::merger::SyncStatus;
+use crate::models::{ConfigHashes, ConfigKind};
+use crate::{commands, db, discovery, encoding, file_system};
+
+pub const CONFIG_FILE_CHANGED_EVENT: &str = "config-file-changed";
+pub const CONFIG_SET_SYNCED_EVENT: &str = "config-set-synced";
+pub const WATCH_FAILED_EVENT: &str = "watch-failed";
+
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Modified,
+    Deleted,
+    Created,
+    Renamed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileChangeEvent {
+    pub workspace_id: i64,
+    pub config_id: i64,
+    pub path: String,
+    pub kind: FileChangeKind,
+    /// New workspace-relative path of a renamed file, if it is still inside the workspace.
+    pub renamed_to: Option<String>,
+    /// Hash of the file on disk; `None` once it is gone.
+    pub current_hash: Option<String>,
+    pub status: SyncStatus,
+    pub has_external_changes: bool,
+}
+
+impl FileChangeEvent {
+    fn new(
+        workspace_id: i64,
+        hashes: &ConfigHashes,
+        path: String,
+        kind: FileChangeKind,
+        renamed_to: Option<String>,
+        current_hash: Option<String>,
+    ) -> Self {
+        let status = hashes.status(current_hash.as_deref());
+        FileChangeEvent {
+            workspace_id,
+            config_id: hashes.id,
+            path,
+            kind,
+            renamed_to,
+            current_hash,
+            status,
+            has_external_changes: matches!(status, SyncStatus::DiskAhead | SyncStatus::Diverged),
+        }
+    }
+}
+
+/// A directory that could not be watched, or an error from the watcher itself.
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchFailure {
+    pub workspace_id: Option<i64>,
+    pub path: Option<String>,
+    pub error: String,
+}
+
+/// Canonical workspace root to workspace id.
+type WatchedRoots = Arc<Mutex<HashMap<PathBuf, i64>>>;
+
+pub struct WorkspaceWatcher {
+    app: AppHandle,
+    debouncer: Mutex<Debouncer<RecommendedWatcher, RecommendedCache>>,
+    roots: WatchedRoots,
+    /// Directories watched per workspace.
+    watched: Mutex<HashMap<i64, HashMap<PathBuf, RecursiveMode>>>,
+}
+
+impl WorkspaceWatcher {
+    pub fn new(app: AppHandle, pool: SqlitePool) -> notify::Result<Self> {
+        let roots: WatchedRoots = Arc::new(Mutex::new(HashMap::new()));
+        let handler_roots = roots.clone();
+        let handler_app = app.clone();
+
+        let debouncer = new_debouncer(DEBOUNCE_TIMEOUT, None, move |result: DebounceEventResult| {
+            let app = &handler_app;
+            let roots = handler_roots.lock().unwrap().clone();
+            let events = match result {
+                Ok(events) => events,
+                Err(errors) => {
+                    for error in errors {
+                        let path = error.paths.first();
+                        let failure = WatchFailure {
+                            workspace_id: path.and_then(|p| locate(&roots, p)).map(|(id, _)| id),
+                            path: path.map(|p| p.display().to_string()),
+                            error: error.to_string(),
+                        };
+                        let _ = app.emit(WATCH_FAILED_EVENT, failure);
+                    }
+                    return;
+                }
+            };
+            let changes: Vec<RawChange> = events
+                .iter()
+                .flat_map(|e| classify(&e.event))
+                .filter_map(without_temp_files)
+                .collect();
+            let (events, set_workspaces) = tauri::async_runtime::block_on(resolve_changes(&pool, &roots, changes));
+            for event in events {
+                let _ = app.emit(CONFIG_FILE_CHANGED_EVENT, event);
+            }
+            for (root, workspace_id) in roots.iter().filter(|(_, id)| set_workspaces.contains(id)) {
+                let root = root.to_string_lossy();
+                let synced = tauri::async_runtime::block_on(commands::sync_workspace_sets(&pool, *workspace_id, &root));
+                let mut added = false;
+                for sync in synced.unwrap_or_default() {
+                    added |= !sync.added.is_empty();
+                    if !sync.added.is_empty() || !sync.missing.is_empty() {
+                        let _ = app.emit(CONFIG_SET_SYNCED_EVENT, sync);
+                    }
+                }
+                // New members can live in directories not watched yet. The
+                // state is only missing while the app is still starting.
+                if added {
+                    if let Some(state) = app.try_state::<commands::AppState>() {
+                        tauri::async_runtime::block_on(commands::rewatch(&state, &pool, *workspace_id));
+                    }
+                }
+            }
+        })?;
+
+        Ok(WorkspaceWatcher {
+            app,
+            debouncer: Mutex::new(debouncer),
+            roots,
+            watched: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Watches the given workspace-relative directories of a workspace, each
+    /// in its mode, in place of what was watched for it before. Directories
+    /// that do not exist are skipped: they only hold missing files, which the
+    /// file status already shows. Failures are emitted as `watch-failed` and
+    /// returned.
+    pub fn watch(&self, workspace_id: i64, root: &str, dirs: &BTreeMap<String, RecursiveMode>) -> Vec<WatchFailure> {
+        let root = match Path::new(root).canonicalize() {
+            Ok(root) => root,
+            Err(e) => return self.report(vec![failure(workspace_id, Path::new(root), e)]),
+        };
+        let wanted: HashMap<PathBuf, RecursiveMode> = dirs
+            .iter()
+            .map(|(dir, mode)| (root.join(dir), *mode))
+            .filter(|(dir, _)| dir.is_dir())
+            .collect();
+
+        let mut watched = self.watched.lock().unwrap();
+        let previous = watched.remove(&workspace_id).unwrap_or_default();
+        let mut debouncer = self.debouncer.lock().unwrap();
+        // Nested workspaces can share a directory
+        for (dir, mode) in &previous {
+            if wanted.get(dir) != Some(mode) && !watched.values().any(|other| other.contains_key(dir)) {
+                let _ = debouncer.unwatch(dir);
+            }
+        }
+
+        let mut failures = Vec::new();
+        let mut now = HashMap::with_capacity(wanted.len());
+        for (dir, mode) in wanted {
+            if previous.get(&dir) != Some(&mode) {
+                if let Err(e) = debouncer.watch(&dir, mode) {
+                    failures.push(failure(workspace_id, &dir, e));
+                    continue;
+                }
+            }
+            now.insert(dir, mode);
+        }
+        watched.insert(workspace_id, now);
+        self.roots.lock().unwrap().insert(root, workspace_id);
+        self.report(failures)
+    }
+
+    pub fn unwatch(&self, workspace_id: i64) {
+        let mut watched = self.watched.lock().unwrap();
+        let mut debouncer = self.debouncer.lock().unwrap();
+        for dir in watched.remove(&workspace_id).unwrap_or_default().into_keys() {
+            if !watched.values().any(|other| other.contains_key(&dir)) {
+                let _ = debouncer.unwatch(&dir);
+            }
+        }
+        self.roots.lock().unwrap().retain(|_, id| *id != workspace_id);
+    }
+
+    fn report(&self, failures: Vec<WatchFailure>) -> Vec<WatchFailure> {
+        for failure in &failures {
+            let _ = self.app.emit(WATCH_FAILED_EVENT, failure);
+        }
+        failures
+    }
+}
+
+fn failure(workspace_id: i64, path: &Path, error: impl std::fmt::Display) -> WatchFailure {
+    WatchFailure {
+        workspace_id: Some(workspace_id),
+        path: Some(path.display().to_string()),
+        error: error.to_string(),
+    }
+}
+
+/// Workspace-relative directories to watch: those holding the workspace's
+/// configs and the files its config sets match, and the fixed prefix of each
+/// set pattern, where new matches appear. A prefix is watched recursively
+/// when the pattern matches below it, and directories inside a recursively
+/// watched one are left out.
+pub async fn watched_dirs(pool: &SqlitePool, workspace_id: i64, root: &str) -> BTreeMap<String, RecursiveMode> {
+    let configs = db::get_config_hashes(pool, workspace_id).await.unwrap_or_default();
+    let sets = db::get_config_sets_by_workspace(pool, workspace_id).await.unwrap_or_default();
+
+    let mut dirs: BTreeMap<String, RecursiveMode> = configs
+        .iter()
+        .map(|c| (parent_dir(&c.path.replace('\\', "/")), RecursiveMode::NonRecursive))
+        .collect();
+    for set in &sets {
+        let matches = discovery::matching_files(root, &set.pattern).unwrap_or_default();
+        dirs.extend(matches.iter().map(|path| (parent_dir(path), RecursiveMode::NonRecursive)));
+    }
+    // After the matches, so a recursive prefix replaces a flat entry
+    for set in &sets {
+        let base = pattern_base(&set.pattern);
+        if matches_below_base(&set.pattern) {
+            dirs.insert(base, RecursiveMode::Recursive);
+        } else {
+            dirs.entry(base).or_insert(RecursiveMode::NonRecursive);
+        }
+    }
+
+    let recursive: Vec<String> = dirs
+        .iter()
+        .filter(|(_, mode)| **mode == RecursiveMode::Recursive)
+        .map(|(dir, _)| dir.clone())
+        .collect();
+    dirs.retain(|dir, _| !recursive.iter().any(|base| is_below(dir, base)));
+    dirs
+}
+
+/// Whether `dir` is strictly inside `base`, both workspace-relative.
+fn is_below(dir: &str, base: &str) -> bool {
+    dir != base && (base.is_empty() || dir.strip_prefix(base).is_some_and(|rest| rest.starts_with('/')))
+}
+
+fn parent_dir(path: &str) -> String {
+    path.rsplit_once('/').map(|(dir, _)| dir.to_string()).unwrap_or_default()
+}
+
+/// Directory components of a glob before the first one with a wildcard.
+fn pattern_base(pattern: &str) -> String {
+    let dir = parent_dir(pattern.trim_start_matches('/'));
+    dir.split('/')
+        .take_while(|component| !component.contains(['*', '?', '[', '{']))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether a glob can match files in subdirectories of its `pattern_base`:
+/// one without a `/` matches at any depth, and a wildcard in a directory
+/// component reaches past the base.
+fn matches_below_base(pattern: &str) -> bool {
+    let anchored = pattern.trim_start_matches('/');
+    if !pattern.starts_with('/') && !anchored.contains('/') {
+        return true;
+    }
+    pattern_base(pattern) != parent_dir(anchored)
+}
+
+/// A filesystem change before it is matched against managed configs.
+#[derive(Debug, PartialEq)]
+struct RawChange {
+    kind: FileChangeKind,
+    path: PathBuf,
+    renamed_to: Option<PathBuf>,
+}
+
+fn classify(event: &Event) -> Vec<RawChange> {
+    let change = |kind, path: &PathBuf| RawChange { kind, path: path.clone(), renamed_to: None };
+
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => vec![RawChange {
+            kind: FileChangeKind::Renamed,
+            path: event.paths[0].clone(),
+            renamed_to: Some(event.paths[1].clone()),
+        }],
+        // Halves of a rename whose other side is outside the watched roots
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            event.paths.iter().map(|p| change(FileChangeKind::Deleted, p)).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            event.paths.iter().map(|p| change(FileChangeKind::Created, p)).collect()
+        }
+        EventKind::Create(_) => event.paths.iter().map(|p| change(FileChangeKind::Created, p)).collect(),
+        EventKind::Modify(_) => event.paths.iter().map(|p| change(FileChangeKind::Modified, p)).collect(),
+        EventKind::Remove(_) => event.paths.iter().map(|p| change(FileChangeKind::Deleted, p)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Drops changes to the temp files of our own atomic writes. Renaming one
+/// over its target is reported as a change of the target.
+fn without_temp_files(change: RawChange) -> Option<RawChange> {
+    if !file_system::is_temp_path(&change.path) {
+        return Some(change);
+    }
+    let target = change.renamed_to?;
+    Some(RawChange { kind: FileChangeKind::Modified, path: target, renamed_to: None })
+}
+
+/// Finds the workspace containing `path` and the path relative to its root.
+fn locate(roots: &HashMap<PathBuf, i64>, path: &Path) -> Option<(i64, String)> {
+    roots
+        .iter()
+        .filter_map(|(root, id)| path.strip_prefix(root).ok().map(|rel| (root, *id, rel)))
+        .max_by_key(|(root, _, _)| root.as_os_str().len())
+        .map(|(_, id, rel)| (id, rel.to_string_lossy().replace('\\', "/")))
+}
+
+/// Matches changes against managed configs. Also returns the workspaces
+/// where a file was added, removed or renamed under a config set's glob.
+async fn resolve_changes(
+    pool: &SqlitePool,
+    roots: &HashMap<PathBuf, i64>,
+    changes: Vec<RawChange>,
+) -> (Vec<FileChangeEvent>, HashSet<i64>) {
+    // Managed paths and config set globs per workspace
+    let mut managed: HashMap<i64, HashMap<String, ConfigHashes>> = HashMap::new();
+    let mut set_patterns: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut events = Vec::new();
+    let mut set_workspaces = HashSet::new();
+
+    for change in changes {
+        let Some((workspace_id, path)) = locate(roots, &change.path) else {
+            continue;
+        };
+        let renamed_to = change.renamed_to.as_deref().and_then(|to| locate(roots, to));
+
+        if let Entry::Vacant(entry) = managed.entry(workspace_id) {
+            let configs = db::get_config_hashes(pool, workspace_id).await.unwrap_or_default();
+            entry.insert(
+                configs
+                    .into_iter()
+                    .map(|hashes| (hashes.path.replace('\\', "/"), hashes))
+                    .collect(),
+            );
+        }
+        let configs = &managed[&workspace_id];
+
+        if change.kind != FileChangeKind::Modified {
+            if let Entry::Vacant(entry) = set_patterns.entry(workspace_id) {
+                let sets = db::get_config_sets_by_workspace(pool, workspace_id).await.unwrap_or_default();
+                entry.insert(sets.into_iter().map(|set| set.pattern).collect());
+            }
+            let to_path = renamed_to.as_ref().filter(|(id, _)| *id == workspace_id).map(|(_, to)| to.as_str());
+            let matches_set = set_patterns[&workspace_id].iter().any(|pattern| {
+                file_system::path_matches_glob(pattern, &path)
+                    || to_path.is_some_and(|to| file_system::path_matches_glob(pattern, to))
+            });
+            if matches_set {
+                set_workspaces.insert(workspace_id);
+            }
+        }
+
+        if let Some(hashes) = configs.get(&path) {
+            let kind = change.kind;
+            let current_hash = match kind {
+                FileChangeKind::Deleted | FileChangeKind::Renamed => None,
+                _ => disk_hash(&change.path, hashes.kind),
+            };
+            events.push(FileChangeEvent::new(
+                workspace_id,
+                hashes,
+                path.clone(),
+                kind,
+                renamed_to.as_ref().map(|(_, to)| to.clone()),
+                current_hash,
+            ));
+        }
+
+        // A file renamed onto a managed path, e.g. an editor's or our own
+        // atomic save, replaces that config's content
+        if let (Some(to), Some((to_workspace, to_path))) = (&change.renamed_to, renamed_to) {
+            let target = managed.get(&to_workspace).and_then(|c| c.get(&to_path));
+            if let Some(hashes) = target {
+                let current_hash = disk_hash(to, hashes.kind);
+                events.push(FileChangeEvent::new(
+                    to_workspace,
+                    hashes,
+                    to_path,
+                    FileChangeKind::Modified,
+                    None,
+                    current_hash,
+                ));
+            }
+        }
+    }
+    (events, set_workspaces)
+}
+
+/// Hash of the file comparable with the config's stored hashes: of the
+/// decoded text for text configs, of the raw bytes for binary ones.
+fn disk_hash(path: &Path, kind: ConfigKind) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    Some(match kind {
+        ConfigKind::Text => file_system::calculate_content_hash(&encoding::decode(&bytes).0),
+        ConfigKind::Binary => file_system::calculate_bytes_hash(&bytes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::CreateKind;
+
+    #[test]
+    fn test_classify_and_locate() {
+        let rename = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(PathBuf::from("/srv/app/.app.env.1-0.tmp"))
+            .add_path(PathBuf::from("/srv/app/app.env"));
+        assert_eq!(
+            classify(&rename),
+            vec![RawChange {
+                kind: FileChangeKind::Renamed,
+                path: PathBuf::from("/srv/app/.app.env.1-0.tmp"),
+                renamed_to: Some(PathBuf::from("/srv/app/app.env")),
+            }]
+        );
+
+        assert_eq!(
+            classify(&rename).into_iter().filter_map(without_temp_files).collect::<Vec<_>>(),
+            vec![RawChange {
+                kind: FileChangeKind::Modified,
+                path: PathBuf::from("/srv/app/app.env"),
+                renamed_to: None,
+            }]
+        );
+        let temp = Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/srv/app/.app.env.1-0.tmp"));
+        assert!(classify(&temp).into_iter().filter_map(without_temp_files).next().is_none());
+
+        let create = Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/srv/app/x"));
+        assert_eq!(classify(&create)[0].kind, FileChangeKind::Created);
+
+        let roots = HashMap::from([(PathBuf::from("/srv"), 1), (PathBuf::from("/srv/app"), 2)]);
+        assert_eq!(locate(&roots, Path::new("/srv/app/conf/db.yaml")), Some((2, "conf/db.yaml".to_string())));
+        assert_eq!(locate(&roots, Path::new("/srv/other.env")), Some((1, "other.env".to_string())));
+        assert_eq!(locate(&roots, Path::new("/etc/hosts")), None);
+    }
+
+    #[test]
+    fn test_pattern_base() {
+        assert_eq!(pattern_base("*.env"), "");
+        assert_eq!(pattern_base("config/*.yaml"), "config");
+        assert_eq!(pattern_base("deploy/prod/app.toml"), "deploy/prod");
+        assert_eq!(pattern_base("services/*/config/*.json"), "services");
+        assert_eq!(pattern_base("**/*.env"), "");
+        assert_eq!(parent_dir("a.env"), "");
+        assert_eq!(parent_dir("conf/db/app.yaml"), "conf/db");
+    }
+
+    #[test]
+    fn test_matches_below_base() {
+        assert!(matches_below_base("*.env"));
+        assert!(matches_below_base("app.yaml"));
+        assert!(!matches_below_base("/app.yaml"));
+        assert!(!matches_below_base("config/*.yaml"));
+        assert!(!matches_below_base("deploy/prod/app.toml"));
+        assert!(matches_below_base("services/*/config/*.yaml"));
+        assert!(matches_below_base("**/*.env"));
+
+        assert!(is_below("services/api/config", "services"));
+        assert!(is_below("services", ""));
+        assert!(!is_below("services", "services"));
+        assert!(!is_below("services-old/config", "services"));
+    }
+}
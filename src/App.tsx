@@ -1,7 +1,16 @@
 import { useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { message } from "@tauri-apps/plugin-dialog";
 import Dashboard from "./pages/Dashboard";
 import useConfigStore from "./stores/configStore";
 
+interface WatchFailure {
+  workspace_id: number | null;
+  path: string | null;
+  error: string;
+}
+
 function App() {
   const theme = useConfigStore(s => s.theme);
   const setEffectiveTheme = useConfigStore(s => s.setEffectiveTheme);
@@ -26,6 +35,28 @@ function App() {
     }
   }, [theme]);
 
+  // 监听失败和审计失败不会打断操作，但需要告诉用户
+  useEffect(() => {
+    const showWatchFailure = (failure: WatchFailure) => {
+      const where = failure.path ? `${failure.path}: ` : '';
+      message(`${where}${failure.error}`, { title: '文件监听失败', kind: 'warning' });
+    };
+
+    invoke<WatchFailure[]>('take_startup_failures')
+      .then(failures => failures.forEach(showWatchFailure))
+      .catch(console.error);
+
+    const unlisteners = [
+      listen<WatchFailure>('watch-failed', e => showWatchFailure(e.payload)),
+      listen<string>('audit-failed', e => {
+        message(e.payload, { title: '审计日志失败', kind: 'error' });
+      }),
+    ];
+    return () => {
+      unlisteners.forEach(p => p.then(unlisten => unlisten()));
+    };
+  }, []);
+
   return <Dashboard />;
 }
 
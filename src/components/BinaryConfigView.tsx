@@ -0,0 +1,91 @@
+import { useEffect, useCallback, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { ask, message } from '@tauri-apps/plugin-dialog';
+import type { Config } from '../stores/configStore';
+
+interface FileBackup {
+  id: number;
+  path: string;
+  size: number;
+  created_at: string;
+}
+
+interface Props {
+  config: Config;
+  onWrite: (type: 'direct' | 'sanitized') => void;
+}
+
+function formatSize(size: number) {
+  if (size < 1024) return `${size} B`;
+  if (size < 1024 * 1024) return `${(size / 1024).toFixed(1)} KB`;
+  return `${(size / 1024 / 1024).toFixed(1)} MB`;
+}
+
+// 二进制配置无法在编辑器中查看，只提供写入和从备份恢复
+function BinaryConfigView({ config, onWrite }: Props) {
+  const [backups, setBackups] = useState<FileBackup[]>([]);
+
+  const loadBackups = useCallback(async () => {
+    try {
+      setBackups(await invoke<FileBackup[]>('get_backups', { workspaceId: config.workspace_id, configId: config.id }));
+    } catch (err) {
+      setBackups([]);
+      await message('获取备份失败: ' + err, { title: '错误', kind: 'error' });
+    }
+  }, [config]);
+
+  useEffect(() => { loadBackups(); }, [loadBackups]);
+
+  const handleRestore = useCallback(async (backup: FileBackup) => {
+    const confirmed = await ask(`确定用 ${backup.created_at} 的备份覆盖 ${backup.path} 吗？当前文件会先被备份。`, {
+      title: '恢复备份', kind: 'warning',
+    });
+    if (!confirmed) return;
+    try {
+      await invoke('restore_backup', { id: backup.id });
+      await message('备份已恢复', { title: '恢复成功' });
+    } catch (err) {
+      await message('恢复备份失败: ' + err, { title: '错误', kind: 'error' });
+    }
+    await loadBackups();
+  }, [loadBackups]);
+
+  return (
+    <div className="flex-1 flex flex-col bg-white dark:bg-gray-900 min-h-0">
+      <div className="px-4 py-2 text-sm border-b border-gray-200 dark:border-gray-700 flex-shrink-0 text-gray-600 dark:text-gray-300">
+        {config.path}：二进制文件，无法在编辑器中查看
+      </div>
+      <div className="flex-1 overflow-auto">
+        <div className="px-4 py-2 text-xs text-gray-500 dark:text-gray-400">备份</div>
+        {backups.length === 0 ? (
+          <p className="px-4 text-sm text-gray-400 dark:text-gray-500">暂无备份</p>
+        ) : (
+          <ul>
+            {backups.map(backup => (
+              <li key={backup.id} className="px-4 py-1.5 flex items-center gap-3 text-sm text-gray-700 dark:text-gray-300">
+                <span>{backup.created_at}</span>
+                <span className="text-xs text-gray-400 dark:text-gray-500">{formatSize(backup.size)}</span>
+                <div className="flex-1" />
+                <button onClick={() => handleRestore(backup)}
+                  className="px-2 py-0.5 text-xs bg-gray-200 hover:bg-gray-300 dark:bg-gray-700 dark:hover:bg-gray-600 rounded">
+                  恢复
+                </button>
+              </li>
+            ))}
+          </ul>
+        )}
+      </div>
+      <div className="px-4 py-2 border-t border-gray-200 dark:border-gray-700 flex gap-2 flex-shrink-0">
+        <button onClick={() => onWrite('direct')} className="px-3 py-1.5 text-sm bg-green-500 hover:bg-green-600 text-white rounded">
+          写入原始
+        </button>
+        <div className="w-px bg-gray-300 dark:bg-gray-600" />
+        <button onClick={() => onWrite('sanitized')} className="px-3 py-1.5 text-sm bg-green-500 hover:bg-green-600 text-white rounded">
+          写入替换文件
+        </button>
+      </div>
+    </div>
+  );
+}
+
+export default BinaryConfigView;
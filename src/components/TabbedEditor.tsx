@@ -1,10 +1,11 @@
-import { useEffect, useRef, useCallback } from 'react';
+import { useEffect, useRef, useCallback, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { ask, message } from '@tauri-apps/plugin-dialog';
 import Editor from '@monaco-editor/react';
 import type * as Monaco from 'monaco-editor';
 import useConfigStore from '../stores/configStore';
 import { useAutoSave } from '../hooks/useAutoSave';
+import BinaryConfigView from './BinaryConfigView';
 
 const LANG_MAP: Record<string, string> = {
   json: 'json', yaml: 'yaml', yml: 'yaml',
@@ -13,8 +14,34 @@ const LANG_MAP: Record<string, string> = {
   html: 'html', css: 'css', xml: 'xml',
 };
 
+// 写入命令在磁盘文件被外部修改时返回的冲突
+interface WriteConflict {
+  config_id: number;
+  path: string;
+  merge: { merged_content: string; has_conflicts: boolean } | null;
+}
+
+type WriteError =
+  | { kind: 'conflict'; message: string; conflicts: WriteConflict[] }
+  | { kind: 'failed'; message: string };
+
+// 合并模式：编辑合并结果，完成后保存为原始内容
+interface MergeState {
+  configId: number;
+  path: string;
+  root: string;
+  content: string;
+  hasConflicts: boolean;
+}
+
+function isWriteError(err: unknown): err is WriteError {
+  return typeof err === 'object' && err !== null && 'kind' in err && 'message' in err;
+}
+
 function TabbedEditor() {
   const selectedConfig = useConfigStore(s => s.selectedConfig);
+  const setSelectedConfig = useConfigStore(s => s.setSelectedConfig);
+  const configs = useConfigStore(s => s.configs);
   const workspaces = useConfigStore(s => s.workspaces);
   const activeTab = useConfigStore(s => s.activeTab);
   const setActiveTab = useConfigStore(s => s.setActiveTab);
@@ -25,9 +52,32 @@ function TabbedEditor() {
   const setAutoSanitize = useConfigStore(s => s.setAutoSanitize);
   const writeScope = useConfigStore(s => s.writeScope);
   const setWriteScope = useConfigStore(s => s.setWriteScope);
+  const sanitizeProfile = useConfigStore(s => s.sanitizeProfile);
+  const setSanitizeProfile = useConfigStore(s => s.setSanitizeProfile);
 
-  const { originalContent, sanitizedContent, loading, handleOriginalChange, handleSanitizedChange } =
-    useAutoSave(selectedConfig, autoSanitize);
+  const {
+    originalContent, sanitizedContent, sanitizedEdited, previewError, loading,
+    handleOriginalChange, handleSanitizedChange,
+  } = useAutoSave(selectedConfig, autoSanitize, sanitizeProfile);
+
+  const [merge, setMerge] = useState<MergeState | null>(null);
+  const [profiles, setProfiles] = useState<string[]>([]);
+
+  // 加载脱敏方案；已删除的方案回退为默认规则
+  useEffect(() => {
+    invoke<{ name: string }[]>('get_profiles')
+      .then(list => {
+        const names = list.map(p => p.name);
+        setProfiles(names);
+        if (sanitizeProfile !== null && !names.includes(sanitizeProfile)) setSanitizeProfile(null);
+      })
+      .catch(() => {});
+  }, [sanitizeProfile, setSanitizeProfile]);
+
+  // 切换到其他配置时退出合并模式
+  useEffect(() => {
+    setMerge(m => (m && m.configId === selectedConfig?.id ? m : null));
+  }, [selectedConfig]);
 
   const monacoTheme = theme === 'dark' ? 'vs-dark' : 'vs-light';
   const isSplit = editorLayout === 'split';
@@ -86,6 +136,65 @@ function TabbedEditor() {
   }, [selectedConfig]);
 
   // --- 写入文件（合并） ---
+  const runWrite = useCallback(async (type: 'direct' | 'sanitized', root: string, force: boolean) => {
+    if (!selectedConfig) return;
+    const label = type === 'direct' ? '原始' : '脱敏';
+    // 二进制配置没有编辑器内容，直接写入已保存的文件
+    if (selectedConfig.kind === 'text') {
+      if (type === 'direct') {
+        await invoke('update_original_content', { id: selectedConfig.id, content: originalContent });
+      } else if (sanitizedEdited) {
+        // 只保存手动编辑过的脱敏内容，否则由后端按当前规则渲染
+        await invoke('update_sanitized_content', { id: selectedConfig.id, content: sanitizedContent });
+      }
+    }
+    const profile = type === 'sanitized' ? sanitizeProfile : undefined;
+    if (writeScope === 'workspace') {
+      const command = type === 'direct' ? 'write_workspace_direct' : 'write_workspace_sanitized';
+      const count = await invoke<number>(command, { workspaceId: selectedConfig.workspace_id, workspaceRoot: root, profile, force });
+      await message(`已写入 ${count} 个${label}文件`, { title: '写入成功' });
+    } else {
+      const command = type === 'direct' ? 'write_to_file_direct' : 'write_to_file_sanitized';
+      await invoke(command, { id: selectedConfig.id, workspaceRoot: root, profile, force });
+      await message(`${label}文件写入成功`, { title: '写入成功' });
+    }
+  }, [selectedConfig, originalContent, sanitizedContent, sanitizedEdited, writeScope, sanitizeProfile]);
+
+  // 冲突时可进入合并，或确认后强制覆盖磁盘上的修改
+  const handleConflict = useCallback(async (err: Extract<WriteError, { kind: 'conflict' }>, type: 'direct' | 'sanitized', root: string) => {
+    const mergeable = err.conflicts.find(c => c.merge);
+    if (mergeable?.merge) {
+      const wantsMerge = await ask(`${err.message}\n\n是否将磁盘上的修改合并到 ${mergeable.path}？`, {
+        title: '写入冲突', kind: 'warning', okLabel: '进入合并', cancelLabel: '其他选项',
+      });
+      if (wantsMerge) {
+        if (mergeable.config_id !== selectedConfig?.id) {
+          const target = configs.find(c => c.id === mergeable.config_id);
+          if (target) setSelectedConfig(target);
+        }
+        setMerge({
+          configId: mergeable.config_id,
+          path: mergeable.path,
+          root,
+          content: mergeable.merge.merged_content,
+          hasConflicts: mergeable.merge.has_conflicts,
+        });
+        return;
+      }
+    }
+
+    const force = await ask(`${err.message}\n\n强制写入将覆盖磁盘上的修改，确定继续吗？`, {
+      title: '写入冲突', kind: 'warning', okLabel: '强制写入', cancelLabel: '取消',
+    });
+    if (!force) return;
+    try {
+      await runWrite(type, root, true);
+    } catch (retryErr) {
+      const detail = isWriteError(retryErr) ? retryErr.message : retryErr;
+      await message('写入文件失败: ' + detail, { title: '错误', kind: 'error' });
+    }
+  }, [selectedConfig, configs, setSelectedConfig, runWrite]);
+
   const handleWrite = useCallback(async (type: 'direct' | 'sanitized') => {
     if (!selectedConfig) return;
     const ws = workspaces.find(w => w.id === selectedConfig.workspace_id);
@@ -98,30 +207,32 @@ function TabbedEditor() {
     if (!confirmed) return;
 
     try {
-      if (writeScope === 'workspace') {
-        if (type === 'direct') {
-          await invoke('update_original_content', { id: selectedConfig.id, content: originalContent });
-          const count = await invoke<number>('write_workspace_direct', { workspaceId: selectedConfig.workspace_id, workspaceRoot: root });
-          await message(`已写入 ${count} 个${label}文件`, { title: '写入成功' });
-        } else {
-          await invoke('update_sanitized_content', { id: selectedConfig.id, content: sanitizedContent });
-          const count = await invoke<number>('write_workspace_sanitized', { workspaceId: selectedConfig.workspace_id, workspaceRoot: root });
-          await message(`已写入 ${count} 个${label}文件`, { title: '写入成功' });
-        }
-      } else {
-        if (type === 'direct') {
-          await invoke('update_original_content', { id: selectedConfig.id, content: originalContent });
-          await invoke('write_to_file_direct', { id: selectedConfig.id, workspaceRoot: root });
-        } else {
-          await invoke('update_sanitized_content', { id: selectedConfig.id, content: sanitizedContent });
-          await invoke('write_to_file_sanitized', { id: selectedConfig.id, workspaceRoot: root });
-        }
-        await message(`${label}文件写入成功`, { title: '写入成功' });
+      await runWrite(type, root, false);
+    } catch (err) {
+      // 写入命令以 { kind, message } 返回错误，冲突时附带合并数据
+      if (isWriteError(err) && err.kind === 'conflict') {
+        await handleConflict(err, type, root);
+        return;
       }
+      const detail = isWriteError(err) ? err.message : err;
+      await message('写入文件失败: ' + detail, { title: '错误', kind: 'error' });
+    }
+  }, [selectedConfig, workspaces, writeScope, runWrite, handleConflict]);
+
+  // 保存合并结果，磁盘版本成为新的同步基线
+  const handleResolveMerge = useCallback(async () => {
+    if (!merge) return;
+    try {
+      await invoke('resolve_conflict', { id: merge.configId, mergedContent: merge.content, workspaceRoot: merge.root });
+      setMerge(null);
+      // 重新选中以加载合并后的内容
+      const merged = configs.find(c => c.id === merge.configId);
+      if (merged) setSelectedConfig({ ...merged });
+      await message('合并完成，可以重新写入文件', { title: '合并完成' });
     } catch (err) {
-      await message('写入文件失败: ' + err, { title: '错误', kind: 'error' });
+      await message('合并失败: ' + err, { title: '错误', kind: 'error' });
     }
-  }, [selectedConfig, workspaces, originalContent, sanitizedContent, writeScope]);
+  }, [merge, configs, setSelectedConfig]);
 
   // --- 编辑器选项 ---
   const editorOptions: Monaco.editor.IStandaloneEditorConstructionOptions = {
@@ -130,6 +241,11 @@ function TabbedEditor() {
   const readOnlyOptions: Monaco.editor.IStandaloneEditorConstructionOptions = {
     ...editorOptions, readOnly: true,
   };
+  const previewBanner = previewError && (
+    <div className="px-3 py-1 text-xs text-red-600 dark:text-red-400 border-b border-gray-200 dark:border-gray-700 flex-shrink-0">
+      {previewError}
+    </div>
+  );
 
   // --- 空状态 / 加载 ---
   if (!selectedConfig) {
@@ -147,6 +263,38 @@ function TabbedEditor() {
     );
   }
 
+  // --- 二进制配置 ---
+  if (selectedConfig.kind === 'binary') {
+    return <BinaryConfigView config={selectedConfig} onWrite={handleWrite} />;
+  }
+
+  // --- 合并模式 ---
+  if (merge) {
+    return (
+      <div className="flex-1 flex flex-col bg-white dark:bg-gray-900 min-h-0">
+        <div className="px-4 py-2 text-sm border-b border-gray-200 dark:border-gray-700 flex-shrink-0 text-orange-600 dark:text-orange-400">
+          合并 {merge.path}：{merge.hasConflicts ? '请解决所有冲突标记后完成合并' : '磁盘上的修改已自动合并，请检查后完成合并'}
+        </div>
+        <div className="flex-1 relative">
+          <div className="absolute inset-0">
+            <Editor height="100%" language={language} value={merge.content}
+              onChange={v => setMerge(m => (m ? { ...m, content: v || '' } : m))}
+              theme={monacoTheme} options={editorOptions} />
+          </div>
+        </div>
+        <div className="px-4 py-2 border-t border-gray-200 dark:border-gray-700 flex gap-2 flex-shrink-0">
+          <button onClick={handleResolveMerge} className="px-3 py-1.5 text-sm bg-green-500 hover:bg-green-600 text-white rounded">
+            完成合并
+          </button>
+          <button onClick={() => setMerge(null)}
+            className="px-3 py-1.5 text-sm bg-gray-200 hover:bg-gray-300 dark:bg-gray-700 dark:hover:bg-gray-600 rounded">
+            取消
+          </button>
+        </div>
+      </div>
+    );
+  }
+
   // --- 渲染 ---
   return (
     <div className="flex-1 flex flex-col bg-white dark:bg-gray-900 min-h-0">
@@ -183,6 +331,17 @@ function TabbedEditor() {
           }`}
           title={autoSanitize ? '当前：自动脱敏（点击切换为手动）' : '当前：手动脱敏（点击切换为自动）'}
         >{autoSanitize ? '自动脱敏' : '手动脱敏'}</button>
+        {profiles.length > 0 && (
+          <select
+            value={sanitizeProfile ?? ''}
+            onChange={e => setSanitizeProfile(e.target.value || null)}
+            className="mx-1 px-1 py-0.5 text-xs self-center bg-transparent text-gray-600 dark:text-gray-300 border border-gray-200 dark:border-gray-700 rounded"
+            title="脱敏方案"
+          >
+            <option value="">默认规则</option>
+            {profiles.map(name => <option key={name} value={name}>{name}</option>)}
+          </select>
+        )}
         <button
           onClick={() => setWriteScope(writeScope === 'workspace' ? 'file' : 'workspace')}
           className={`px-3 py-2 text-xs self-center ${
@@ -213,6 +372,7 @@ function TabbedEditor() {
             <div className="px-3 py-1 text-xs text-gray-500 dark:text-gray-400 border-b border-gray-200 dark:border-gray-700 flex-shrink-0">
               脱敏{autoSanitize ? '（只读）' : ''}
             </div>
+            {previewBanner}
             <div className="flex-1 relative">
               <div className="absolute inset-0">
                 <Editor height="100%" language={language} value={sanitizedContent} onChange={handleSanitizedChange}
@@ -222,15 +382,18 @@ function TabbedEditor() {
           </div>
         </div>
       ) : (
-        <div className="flex-1 relative">
-          <div className="absolute inset-0">
-            {activeTab === 'original' ? (
-              <Editor height="100%" language={language} value={originalContent} onChange={handleOriginalChange}
-                theme={monacoTheme} options={editorOptions} />
-            ) : (
-              <Editor height="100%" language={language} value={sanitizedContent} onChange={handleSanitizedChange}
-                theme={monacoTheme} options={autoSanitize ? readOnlyOptions : editorOptions} />
-            )}
+        <div className="flex-1 flex flex-col min-h-0">
+          {activeTab === 'sanitized' && previewBanner}
+          <div className="flex-1 relative">
+            <div className="absolute inset-0">
+              {activeTab === 'original' ? (
+                <Editor height="100%" language={language} value={originalContent} onChange={handleOriginalChange}
+                  theme={monacoTheme} options={editorOptions} />
+              ) : (
+                <Editor height="100%" language={language} value={sanitizedContent} onChange={handleSanitizedChange}
+                  theme={monacoTheme} options={autoSanitize ? readOnlyOptions : editorOptions} />
+              )}
+            </div>
           </div>
         </div>
       )}
@@ -1,16 +1,21 @@
 import { useEffect, useRef, useState, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import type { Config } from '../stores/configStore';
+import type { Config, ConfigContent } from '../stores/configStore';
 
 const AUTO_SAVE_DELAY = 1500;
 const AUTO_SANITIZE_DELAY = 800;
 
-export function useAutoSave(selectedConfig: Config | null, autoSanitize: boolean) {
+export function useAutoSave(selectedConfig: Config | null, autoSanitize: boolean, profile: string | null) {
   const [originalContent, setOriginalContent] = useState('');
   const [sanitizedContent, setSanitizedContent] = useState('');
+  const [previewError, setPreviewError] = useState<string | null>(null);
+  // 用户手动编辑过脱敏内容；未编辑时脱敏结果由后端渲染，不保存为手动副本
+  const [sanitizedEdited, setSanitizedEdited] = useState(false);
   const [loading, setLoading] = useState(false);
 
   const configIdRef = useRef<number | null>(null);
+  const profileRef = useRef(profile);
+  profileRef.current = profile;
   const isInitialLoad = useRef(true);
   const saveTimer = useRef<ReturnType<typeof setTimeout> | null>(null);
   const sanitizeTimer = useRef<ReturnType<typeof setTimeout> | null>(null);
@@ -20,44 +25,70 @@ export function useAutoSave(selectedConfig: Config | null, autoSanitize: boolean
     if (sanitizeTimer.current) { clearTimeout(sanitizeTimer.current); sanitizeTimer.current = null; }
   }, []);
 
+  // 加载脱敏预览；无法脱敏时显示错误并清空，不能用原始内容代替
+  const loadPreview = useCallback(async (id: number) => {
+    try {
+      const s = await invoke<string>('get_sanitized_preview', { id, profile: profileRef.current });
+      if (configIdRef.current !== id) return;
+      setSanitizedContent(s);
+      setPreviewError(null);
+    } catch (err) {
+      if (configIdRef.current !== id) return;
+      setSanitizedContent('');
+      setPreviewError(String(err));
+    }
+  }, []);
+
   // 加载内容
   useEffect(() => {
     clearTimers();
-    if (!selectedConfig) {
+    setPreviewError(null);
+    setSanitizedEdited(false);
+    // 二进制配置不在编辑器中加载，也不自动保存
+    if (!selectedConfig || selectedConfig.kind === 'binary') {
       configIdRef.current = null;
       setOriginalContent('');
       setSanitizedContent('');
+      setLoading(false);
       return;
     }
-    configIdRef.current = selectedConfig.id;
+    const id = selectedConfig.id;
+    configIdRef.current = id;
     isInitialLoad.current = true;
     setLoading(true);
-    setOriginalContent(selectedConfig.original_content);
+    setOriginalContent('');
     setSanitizedContent('');
 
-    invoke<string>('get_sanitized_preview', { id: selectedConfig.id })
-      .then(s => {
-        if (configIdRef.current === selectedConfig.id) setSanitizedContent(s);
-      })
-      .catch(() => {
-        if (configIdRef.current === selectedConfig.id) setSanitizedContent(selectedConfig.original_content);
-      })
-      .finally(() => {
+    (async () => {
+      let original: string | null = null;
+      try {
+        const config = await invoke<ConfigContent | null>('get_config_by_id', { id });
+        original = config?.original_content ?? null;
+      } catch {}
+      if (configIdRef.current !== id) return;
+      if (original === null) {
+        // 内容加载失败时不自动保存，避免空内容覆盖原始内容
+        configIdRef.current = null;
+        setLoading(false);
+        return;
+      }
+      setOriginalContent(original);
+      await loadPreview(id);
+      if (configIdRef.current === id) {
         setLoading(false);
         requestAnimationFrame(() => { isInitialLoad.current = false; });
-      });
+      }
+    })();
 
     return clearTimers;
-  }, [selectedConfig, clearTimers]);
+  }, [selectedConfig, clearTimers, loadPreview]);
 
-  // 切换自动脱敏时，立即刷新预览
+  // 切换自动脱敏或脱敏方案时，立即刷新预览
   useEffect(() => {
     if (autoSanitize && selectedConfig && configIdRef.current === selectedConfig.id) {
-      invoke<string>('get_sanitized_preview', { id: selectedConfig.id })
-        .then(s => { if (configIdRef.current === selectedConfig.id) setSanitizedContent(s); })
-        .catch(() => {});
+      loadPreview(selectedConfig.id);
     }
-  }, [autoSanitize, selectedConfig]);
+  }, [autoSanitize, selectedConfig, profile, loadPreview]);
 
   // 原始内容变更：统一 debounce 链路，保存 + 脱敏走同一个 timer 消除竞态
   const handleOriginalChange = useCallback((value: string | undefined) => {
@@ -74,8 +105,7 @@ export function useAutoSave(selectedConfig: Config | null, autoSanitize: boolean
       sanitizeTimer.current = setTimeout(async () => {
         try {
           await invoke('update_original_content', { id, content: v });
-          const s = await invoke<string>('get_sanitized_preview', { id });
-          if (configIdRef.current === id) setSanitizedContent(s);
+          await loadPreview(id);
         } catch {}
       }, AUTO_SANITIZE_DELAY);
     } else {
@@ -84,7 +114,7 @@ export function useAutoSave(selectedConfig: Config | null, autoSanitize: boolean
         try { await invoke('update_original_content', { id, content: v }); } catch {}
       }, AUTO_SAVE_DELAY);
     }
-  }, [autoSanitize, clearTimers]);
+  }, [autoSanitize, clearTimers, loadPreview]);
 
   // 脱敏内容变更（仅手动模式有效）
   const handleSanitizedChange = useCallback((value: string | undefined) => {
@@ -92,6 +122,7 @@ export function useAutoSave(selectedConfig: Config | null, autoSanitize: boolean
     setSanitizedContent(v);
     if (isInitialLoad.current || !configIdRef.current || autoSanitize) return;
     const id = configIdRef.current;
+    setSanitizedEdited(true);
 
     if (saveTimer.current) { clearTimeout(saveTimer.current); saveTimer.current = null; }
     saveTimer.current = setTimeout(async () => {
@@ -99,5 +130,8 @@ export function useAutoSave(selectedConfig: Config | null, autoSanitize: boolean
     }, AUTO_SAVE_DELAY);
   }, [autoSanitize]);
 
-  return { originalContent, sanitizedContent, loading, handleOriginalChange, handleSanitizedChange };
+  return {
+    originalContent, sanitizedContent, sanitizedEdited, previewError, loading,
+    handleOriginalChange, handleSanitizedChange,
+  };
 }
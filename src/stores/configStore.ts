@@ -8,11 +8,16 @@ export interface Workspace {
   root_path: string;
 }
 
+// 列表中的配置只含元数据，内容在打开时加载
 export interface Config {
   id: number;
   workspace_id: number;
   name: string;
   path: string;
+  kind: 'text' | 'binary';
+}
+
+export interface ConfigContent extends Config {
   original_content: string;
   sanitized_content: string | null;
 }
@@ -45,6 +50,10 @@ interface ConfigStore {
   writeScope: 'file' | 'workspace';
   setWriteScope: (scope: 'file' | 'workspace') => void;
 
+  // 脱敏方案名称，null 为默认规则
+  sanitizeProfile: string | null;
+  setSanitizeProfile: (profile: string | null) => void;
+
   theme: Theme;
   setTheme: (theme: Theme) => void;
 
@@ -106,6 +115,16 @@ const useConfigStore = create<ConfigStore>((set) => ({
     set({ writeScope: scope });
   },
 
+  sanitizeProfile: localStorage.getItem('sanitizeProfile'),
+  setSanitizeProfile: (profile) => {
+    if (profile === null) {
+      localStorage.removeItem('sanitizeProfile');
+    } else {
+      localStorage.setItem('sanitizeProfile', profile);
+    }
+    set({ sanitizeProfile: profile });
+  },
+
   theme: (localStorage.getItem('theme') as Theme) || 'system',
   setTheme: (theme) => {
     localStorage.setItem('theme', theme);